@@ -0,0 +1,124 @@
+use crate::parser::parse;
+use crate::shell::Shell;
+use crate::types::command::{Command, CommandList, CompoundCommand, SimpleCommand};
+use crate::types::error::ShellError;
+use crate::types::word::Word;
+
+/// Whether the name can be defined as an alias, which excludes blanks, quotes and the characters special to the parser
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.chars().any(|ch| ch.is_whitespace() || "/$`=|&;()<>'\"\\".contains(ch))
+}
+
+/// Whether the word is an alias that is neither replaced already nor being executed
+fn can_expand(shell: &Shell, replaced: &[String], name: &str) -> bool {
+    shell.aliases.contains_key(name)
+        && !replaced.iter().any(|other| other == name)
+        && !shell.aliases_in_use.iter().any(|other| other == name)
+}
+
+/// Returns the command whose name is replaced with the value of the alias if it is one,
+/// along with the names of the aliases replaced
+///
+/// The value is replaced again if it also begins with an alias,
+/// and the word following it is also replaced if the value ends with a blank, e.g., `alias sudo='sudo '`.
+/// Each alias is replaced only once, so `alias ls='ls -F'` doesn't loop.
+pub fn expand(shell: &Shell, cmd: &SimpleCommand) -> Result<Option<(Command, Vec<String>)>, ShellError> {
+    if !cmd.args.first().and_then(Word::as_literal).is_some_and(|name| can_expand(shell, &[], name)) {
+        return Ok(None);
+    }
+    let mut cmd = cmd.clone();
+    let mut replaced: Vec<String> = vec![];
+    let mut i = 0;
+    // the index of the word checked after the one at `i` is settled, which follows a value ending with a blank
+    let mut next = None;
+
+    loop {
+        let name = match cmd.args.get(i).and_then(Word::as_literal) {
+            Some(name) if can_expand(shell, &replaced, name) => name.to_string(),
+            _ => match next.take() {
+                Some(j) if j > i => {
+                    i = j;
+                    continue;
+                },
+                _ => break,
+            },
+        };
+        let value = &shell.aliases[&name];
+        let list = parse(&format!("{value}\n")).map_err(|err| ShellError::Expansion(format!("{name}: {err}")))?;
+
+        let words = match into_simple(list) {
+            Ok(simple) if i == 0 => {
+                cmd.assigns.extend(simple.assigns);
+                cmd.redirects.splice(0..0, simple.redirects);
+                simple.args
+            },
+            // Only words replace an argument.
+            Ok(simple) if simple.assigns.is_empty() && simple.redirects.is_empty() => simple.args,
+            // e.g., `alias ll='ls -l | less'`, which is run as a group with the rest of the command
+            Err(mut list) if i == 0 => {
+                if !attach(&mut list, cmd) {
+                    return Err(ShellError::Expansion(format!("{name}: syntax error in the value of the alias")));
+                }
+                replaced.push(name);
+                let body = CompoundCommand::BraceGroup(list);
+                return Ok(Some((Command::Compound { body, redirects: vec![] }, replaced)));
+            },
+            _ => break,
+        };
+
+        let len = words.len();
+        cmd.args.splice(i..=i, words);
+        if let Some(j) = &mut next
+            && *j > i {
+            *j = *j + len - 1;
+        }
+        if value.ends_with([' ', '\t']) {
+            next = Some(i + len);
+        }
+        replaced.push(name);
+    }
+    Ok(Some((Command::Simple(cmd), replaced)))
+}
+
+/// Returns the command if the list consists of a simple command only, or the list as is otherwise
+fn into_simple(mut list: CommandList) -> Result<SimpleCommand, CommandList> {
+    if list.items.is_empty() {
+        return Ok(SimpleCommand::default());
+    }
+    if let [item] = list.items.as_mut_slice()
+        && item.rest.is_empty()
+        && !item.background
+        && !item.first.negated
+        && let [Command::Simple(simple)] = item.first.cmds.as_mut_slice() {
+        return Ok(std::mem::take(simple));
+    }
+    Err(list)
+}
+
+/// Moves the assignments of the command to the first command of the list,
+/// and the arguments and the redirections to the last one
+///
+/// Returns false if they can't be attached since the command is not a simple one, e.g., `{ ...; }`.
+fn attach(list: &mut CommandList, cmd: SimpleCommand) -> bool {
+    if !cmd.assigns.is_empty() {
+        match list.items.first_mut().and_then(|item| item.first.cmds.first_mut()) {
+            Some(Command::Simple(first)) => {
+                first.assigns.splice(0..0, cmd.assigns);
+            },
+            _ => return false,
+        }
+    }
+    if cmd.args.len() > 1 || !cmd.redirects.is_empty() {
+        let last = list.items.last_mut()
+            .map(|item| item.rest.last_mut().map_or(&mut item.first, |(_, pipeline)| pipeline))
+            .and_then(|pipeline| pipeline.cmds.last_mut());
+        match last {
+            Some(Command::Simple(last)) => {
+                last.args.extend(cmd.args.into_iter().skip(1));
+                last.redirects.extend(cmd.redirects);
+            },
+            _ => return false,
+        }
+    }
+    true
+}
@@ -0,0 +1,368 @@
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use crate::shell::Shell;
+use crate::types::error::ShellError;
+
+/// How deep the values of variables can be evaluated as expressions, e.g., `a=b b=c c=1`
+const MAX_DEPTH: usize = 1024;
+
+/// Operators, where the longer ones come first so that they are matched first
+const OPERATORS: [&str; 39] = [
+    "<<=", ">>=",
+    "++", "--", "**", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||",
+    "*=", "/=", "%=", "+=", "-=", "&=", "^=", "|=",
+    "+", "-", "*", "/", "%", "<", ">", "&", "^", "|", "!", "~", "?", ":", "=", ",", "(", ")",
+];
+
+enum Token {
+    Num(i64),
+    Name(String),
+    Op(&'static str),
+}
+
+enum Expr {
+    Num(i64),
+    Var(String),
+    /// `-x`, `+x`, `!x` or `~x`
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    /// `NAME = x` or `NAME op= x`, where `op` is `None` for `=`
+    Assign(String, Option<&'static str>, Box<Expr>),
+    /// `++NAME`, `--NAME`, `NAME++` or `NAME--`
+    IncDec { name: String, delta: i64, prefix: bool },
+    /// `cond ? x : y`
+    Cond(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+/// Evaluates the arithmetic expression with 64-bit integers, where variables are referred to by name
+///
+/// An empty expression is 0.
+pub fn eval(shell: &mut Shell, text: &str) -> Result<i64, ShellError> {
+    eval_nested(shell, text, 0)
+}
+
+fn eval_nested(shell: &mut Shell, text: &str, depth: usize) -> Result<i64, ShellError> {
+    if depth > MAX_DEPTH {
+        return Err(ShellError::Expansion(format!("{text}: expression recursion level exceeded")));
+    }
+    let tokens = tokenize(text)?;
+    if tokens.is_empty() {
+        return Ok(0);
+    }
+    let mut parser = Parser { tokens: tokens.into_iter().peekable(), text };
+    let expr = parser.parse_comma()?;
+    if let Some(token) = parser.tokens.next() {
+        return Err(parser.syntax_error(Some(&token)));
+    }
+    Evaluator { shell, text, depth }.eval(&expr)
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, ShellError> {
+    let mut tokens = vec![];
+    let mut chars = text.char_indices().peekable();
+
+    while let Some(&(i, ch)) = chars.peek() {
+        if ch.is_whitespace() {
+            chars.next();
+        } else if ch.is_ascii_digit() {
+            tokens.push(Token::Num(parse_number(&mut chars, text)?));
+        } else if ch.is_ascii_alphabetic() || ch == '_' {
+            let mut name = String::new();
+            while let Some((_, ch)) = chars.next_if(|&(_, ch)| ch.is_ascii_alphanumeric() || ch == '_') {
+                name.push(ch);
+            }
+            tokens.push(Token::Name(name));
+        } else {
+            let Some(op) = OPERATORS.iter().find(|op| text[i..].starts_with(**op)) else {
+                return Err(ShellError::Expansion(format!(
+                    "{text}: syntax error: invalid arithmetic operator (error token is \"{}\")",
+                    &text[i..],
+                )));
+            };
+            for _ in 0..op.len() {
+                chars.next();
+            }
+            tokens.push(Token::Op(op));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parses a decimal, `0x` hexadecimal, `0` octal, or `BASE#N` number for a base from 2 to 64,
+/// where the digits above 9 are `a`-`z`, `A`-`Z`, `@` and `_`.
+fn parse_number(chars: &mut Peekable<CharIndices>, text: &str) -> Result<i64, ShellError> {
+    let mut literal = String::new();
+    while let Some((_, ch)) = chars.next_if(|&(_, ch)| ch.is_ascii_alphanumeric() || "#@_".contains(ch)) {
+        literal.push(ch);
+    }
+
+    let (base, digits) = if let Some((base, digits)) = literal.split_once('#') {
+        match base.parse::<u32>() {
+            Ok(base) if (2..=64).contains(&base) => (base, digits),
+            _ => {
+                return Err(ShellError::Expansion(format!("{text}: invalid arithmetic base (error token is \"{literal}\")")));
+            },
+        }
+    } else if let Some(digits) = literal.strip_prefix("0x").or_else(|| literal.strip_prefix("0X")) {
+        (16, digits)
+    } else if literal.len() > 1 && literal.starts_with('0') {
+        (8, &literal[1..])
+    } else {
+        (10, literal.as_str())
+    };
+
+    let too_great = || ShellError::Expansion(format!("{text}: value too great for base (error token is \"{literal}\")"));
+    if digits.is_empty() {
+        return Err(too_great());
+    }
+    let mut value: i64 = 0;
+    for ch in digits.chars() {
+        let digit = match ch {
+            '0'..='9' => ch as u32 - '0' as u32,
+            'a'..='z' => ch as u32 - 'a' as u32 + 10,
+            'A'..='Z' if base <= 36 => ch as u32 - 'A' as u32 + 10,
+            'A'..='Z' => ch as u32 - 'A' as u32 + 36,
+            '@' => 62,
+            _ => 63,
+        };
+        if digit >= base {
+            return Err(too_great());
+        }
+        value = value.wrapping_mul(base as i64).wrapping_add(digit as i64);
+    }
+    Ok(value)
+}
+
+/// A precedence climbing parser, where the binary operators are listed from the lowest precedence
+struct Parser<'a> {
+    tokens: Peekable<std::vec::IntoIter<Token>>,
+    text: &'a str,
+}
+
+const BINARY_LEVELS: [&[&str]; 10] = [
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<=", ">=", "<", ">"],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+impl Parser<'_> {
+    fn syntax_error(&self, token: Option<&Token>) -> ShellError {
+        let token = match token {
+            Some(Token::Num(n)) => n.to_string(),
+            Some(Token::Name(name)) => name.clone(),
+            Some(Token::Op(op)) => op.to_string(),
+            None => String::new(),
+        };
+        match token.is_empty() {
+            true => ShellError::Expansion(format!("{}: syntax error: operand expected", self.text)),
+            false => {
+                ShellError::Expansion(format!("{}: syntax error in expression (error token is \"{token}\")", self.text))
+            },
+        }
+    }
+
+    fn next_op_in(&mut self, ops: &[&str]) -> Option<&'static str> {
+        match self.tokens.peek() {
+            Some(Token::Op(op)) if ops.contains(op) => {
+                let op = *op;
+                self.tokens.next();
+                Some(op)
+            },
+            _ => None,
+        }
+    }
+
+    fn expect_op(&mut self, expected: &str) -> Result<(), ShellError> {
+        match self.tokens.next() {
+            Some(Token::Op(op)) if op == expected => Ok(()),
+            token => Err(self.syntax_error(token.as_ref())),
+        }
+    }
+
+    /// `x, y`
+    fn parse_comma(&mut self) -> Result<Expr, ShellError> {
+        let mut lhs = self.parse_assign()?;
+        while self.next_op_in(&[","]).is_some() {
+            lhs = Expr::Binary(",", Box::new(lhs), Box::new(self.parse_assign()?));
+        }
+        Ok(lhs)
+    }
+
+    /// `NAME = x` and `NAME op= x`, which are right associative
+    fn parse_assign(&mut self) -> Result<Expr, ShellError> {
+        let lhs = self.parse_cond()?;
+        let Some(op) = self.next_op_in(&["=", "*=", "/=", "%=", "+=", "-=", "<<=", ">>=", "&=", "^=", "|="]) else {
+            return Ok(lhs);
+        };
+        let Expr::Var(name) = lhs else {
+            return Err(ShellError::Expansion(format!(
+                "{}: attempted assignment to non-variable (error token is \"{op}\")",
+                self.text,
+            )));
+        };
+        let rhs = self.parse_assign()?;
+        Ok(Expr::Assign(name, op.strip_suffix('=').filter(|op| !op.is_empty()), Box::new(rhs)))
+    }
+
+    /// `cond ? x : y`, which is right associative
+    fn parse_cond(&mut self) -> Result<Expr, ShellError> {
+        let cond = self.parse_binary(0)?;
+        if self.next_op_in(&["?"]).is_none() {
+            return Ok(cond);
+        }
+        let then = self.parse_comma()?;
+        self.expect_op(":")?;
+        let otherwise = self.parse_assign()?;
+        Ok(Expr::Cond(Box::new(cond), Box::new(then), Box::new(otherwise)))
+    }
+
+    fn parse_binary(&mut self, level: usize) -> Result<Expr, ShellError> {
+        let Some(ops) = BINARY_LEVELS.get(level) else {
+            return self.parse_power();
+        };
+        let mut lhs = self.parse_binary(level + 1)?;
+        while let Some(op) = self.next_op_in(ops) {
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.parse_binary(level + 1)?));
+        }
+        Ok(lhs)
+    }
+
+    /// `x ** y`, which is right associative
+    fn parse_power(&mut self) -> Result<Expr, ShellError> {
+        let base = self.parse_unary()?;
+        match self.next_op_in(&["**"]) {
+            Some(op) => Ok(Expr::Binary(op, Box::new(base), Box::new(self.parse_power()?))),
+            None => Ok(base),
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, ShellError> {
+        if let Some(op) = self.next_op_in(&["++", "--"]) {
+            return match self.tokens.next() {
+                Some(Token::Name(name)) => Ok(Expr::IncDec { name, delta: if op == "++" { 1 } else { -1 }, prefix: true }),
+                token => Err(self.syntax_error(token.as_ref())),
+            };
+        }
+        if let Some(op) = self.next_op_in(&["-", "+", "!", "~"]) {
+            return Ok(Expr::Unary(op, Box::new(self.parse_unary()?)));
+        }
+        self.parse_postfix()
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expr, ShellError> {
+        match self.tokens.next() {
+            Some(Token::Num(n)) => Ok(Expr::Num(n)),
+            Some(Token::Name(name)) => match self.next_op_in(&["++", "--"]) {
+                Some(op) => Ok(Expr::IncDec { name, delta: if op == "++" { 1 } else { -1 }, prefix: false }),
+                None => Ok(Expr::Var(name)),
+            },
+            Some(Token::Op("(")) => {
+                let expr = self.parse_comma()?;
+                self.expect_op(")")?;
+                Ok(expr)
+            },
+            token => Err(self.syntax_error(token.as_ref())),
+        }
+    }
+}
+
+struct Evaluator<'a> {
+    shell: &'a mut Shell,
+    /// the expression, which is shown in the error messages
+    text: &'a str,
+    depth: usize,
+}
+
+impl Evaluator<'_> {
+    fn eval(&mut self, expr: &Expr) -> Result<i64, ShellError> {
+        match expr {
+            Expr::Num(n) => Ok(*n),
+            Expr::Var(name) => self.get(name),
+            Expr::Unary(op, operand) => {
+                let value = self.eval(operand)?;
+                Ok(match *op {
+                    "-" => value.wrapping_neg(),
+                    "!" => (value == 0) as i64,
+                    "~" => !value,
+                    _ => value,
+                })
+            },
+            // The right operand is only evaluated if needed.
+            Expr::Binary("&&", lhs, rhs) => Ok((self.eval(lhs)? != 0 && self.eval(rhs)? != 0) as i64),
+            Expr::Binary("||", lhs, rhs) => Ok((self.eval(lhs)? != 0 || self.eval(rhs)? != 0) as i64),
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = self.eval(lhs)?;
+                let rhs = self.eval(rhs)?;
+                apply(op, lhs, rhs).map_err(|err| ShellError::Expansion(format!("{}: {err}", self.text)))
+            },
+            Expr::Assign(name, op, rhs) => {
+                let rhs = self.eval(rhs)?;
+                let value = match op {
+                    Some(op) => apply(op, self.get(name)?, rhs)
+                        .map_err(|err| ShellError::Expansion(format!("{}: {err}", self.text)))?,
+                    None => rhs,
+                };
+                self.shell.vars.set(name, value.to_string());
+                Ok(value)
+            },
+            Expr::IncDec { name, delta, prefix } => {
+                let old = self.get(name)?;
+                let new = old.wrapping_add(*delta);
+                self.shell.vars.set(name, new.to_string());
+                Ok(if *prefix { new } else { old })
+            },
+            Expr::Cond(cond, then, otherwise) => match self.eval(cond)? {
+                0 => self.eval(otherwise),
+                _ => self.eval(then),
+            },
+        }
+    }
+
+    /// Returns the value of the variable, which is evaluated as an expression, or 0 if it is unset or empty
+    fn get(&mut self, name: &str) -> Result<i64, ShellError> {
+        let value = match self.shell.vars.get(name) {
+            Some(value) => value.trim().to_string(),
+            None if self.shell.opts.nounset => return Err(ShellError::Expansion(format!("{name}: unbound variable"))),
+            None => return Ok(0),
+        };
+        if let Ok(n) = value.parse() {
+            return Ok(n);
+        }
+        eval_nested(self.shell, &value, self.depth + 1)
+    }
+}
+
+/// Applies the binary operator, wrapping around on overflow
+fn apply(op: &str, lhs: i64, rhs: i64) -> Result<i64, &'static str> {
+    Ok(match op {
+        "+" => lhs.wrapping_add(rhs),
+        "-" => lhs.wrapping_sub(rhs),
+        "*" => lhs.wrapping_mul(rhs),
+        "/" | "%" if rhs == 0 => return Err("division by 0"),
+        "/" => lhs.wrapping_div(rhs),
+        "%" => lhs.wrapping_rem(rhs),
+        "**" if rhs < 0 => return Err("exponent less than 0"),
+        "**" => lhs.wrapping_pow(rhs.min(u32::MAX as i64) as u32),
+        "<<" => lhs.wrapping_shl(rhs as u32),
+        ">>" => lhs.wrapping_shr(rhs as u32),
+        "<" => (lhs < rhs) as i64,
+        ">" => (lhs > rhs) as i64,
+        "<=" => (lhs <= rhs) as i64,
+        ">=" => (lhs >= rhs) as i64,
+        "==" => (lhs == rhs) as i64,
+        "!=" => (lhs != rhs) as i64,
+        "&" => lhs & rhs,
+        "^" => lhs ^ rhs,
+        "|" => lhs | rhs,
+        // `,`
+        _ => rhs,
+    })
+}
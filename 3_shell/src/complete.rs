@@ -0,0 +1,282 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use crate::editor::Completion;
+use crate::executor::{call_function_by_name, BUILTIN_NAMES};
+use crate::parser::quote;
+use crate::shell::Shell;
+use crate::types::word::is_valid_name;
+
+/// How the arguments of a command are completed, which is set by `complete`
+#[derive(Clone)]
+pub enum CompletionSpec {
+    /// `-W WORDS`, the words separated by blanks
+    Words(String),
+    /// `-F FUNC`, which is called with the command name, the word being completed and the previous word,
+    /// and sets `COMPREPLY` to the candidates
+    Function(String),
+}
+
+/// Words after which a command is expected
+const COMMAND_PREFIXES: [&str; 10] = ["if", "then", "elif", "else", "while", "until", "do", "{", "!", "time"];
+
+/// What the word being completed is
+enum Kind {
+    Command,
+    /// an argument of the command, where the words of the command up to the cursor are given
+    Argument(Vec<String>),
+    /// the target of a redirection
+    Path,
+}
+
+/// Returns the candidates for the word ending at the cursor, which is the index of a character
+pub fn complete(shell: &mut Shell, text: &str, cursor: usize) -> Completion {
+    let chars: Vec<char> = text.chars().collect();
+    let (start, kind) = scan(&chars[..cursor]);
+    let word = unquote(&chars[start..cursor]);
+
+    let candidates = if let Some(prefix) = word.strip_prefix('$') {
+        complete_variables(shell, prefix)
+    } else {
+        match kind {
+            Kind::Command if !word.contains('/') => complete_commands(shell, &word),
+            Kind::Command => complete_paths(shell, &word, true),
+            Kind::Argument(words) => match complete_with_spec(shell, &words, &word, text, cursor) {
+                Some(candidates) => candidates,
+                None => complete_paths(shell, &word, false),
+            },
+            Kind::Path => complete_paths(shell, &word, false),
+        }
+    };
+    let candidates = candidates.into_iter()
+        .map(|candidate| match candidate.strip_suffix('/') {
+            // the slash of a directory is left outside the quotes, so that its entries can be completed next
+            Some(dir) => format!("{}/", quote(dir)),
+            None if candidate.starts_with('$') => candidate,
+            None => quote(&candidate),
+        })
+        .collect();
+    Completion { start, candidates }
+}
+
+/// Returns where the word ending at the end of the text begins and what it is,
+/// where the text is split roughly like the parser does
+fn scan(chars: &[char]) -> (usize, Kind) {
+    // the words of the current simple command, except the targets of the redirections
+    let mut words: Vec<String> = vec![];
+    let mut word_start: Option<usize> = None;
+    let mut quote = None;
+    let mut after_redirect = false;
+    let mut i = 0;
+
+    let end_word = |end: usize, word_start: &mut Option<usize>, after_redirect: &mut bool, words: &mut Vec<String>| {
+        if let Some(start) = word_start.take() {
+            match *after_redirect {
+                true => *after_redirect = false,
+                false => words.push(chars[start..end].iter().collect()),
+            }
+        }
+    };
+
+    while i < chars.len() {
+        let ch = chars[i];
+        match (ch, quote) {
+            // the escaped character is a part of the word
+            ('\\', None | Some('"')) => {
+                word_start.get_or_insert(i);
+                i += 1;
+            },
+            (_, Some(q)) => {
+                if ch == q {
+                    quote = None;
+                }
+            },
+            ('\'' | '"', None) => {
+                quote = Some(ch);
+                word_start.get_or_insert(i);
+            },
+            (' ' | '\t', None) => end_word(i, &mut word_start, &mut after_redirect, &mut words),
+            ('|' | '&' | ';' | '(' | ')' | '<' | '>' | '\n', None) => {
+                let op_start = i;
+                while i + 1 < chars.len() && "|&;<>".contains(chars[i + 1]) && chars[i] != '\n' {
+                    i += 1;
+                }
+                let op: String = chars[op_start..=i].iter().collect();
+                if op.contains(['<', '>']) {
+                    // the digits of `2>` are not an argument
+                    if word_start.is_some_and(|start| chars[start..op_start].iter().all(char::is_ascii_digit)) {
+                        word_start = None;
+                    }
+                    end_word(op_start, &mut word_start, &mut after_redirect, &mut words);
+                    after_redirect = true;
+                } else {
+                    end_word(op_start, &mut word_start, &mut after_redirect, &mut words);
+                    words.clear();
+                    after_redirect = false;
+                }
+            },
+            _ => {
+                word_start.get_or_insert(i);
+            },
+        }
+        i += 1;
+    }
+
+    let start = word_start.unwrap_or(chars.len());
+    if after_redirect {
+        return (start, Kind::Path);
+    }
+    // the assignments and the reserved words before the command name
+    let skipped = words.iter()
+        .take_while(|word| {
+            COMMAND_PREFIXES.contains(&word.as_str())
+                || word.split_once('=').is_some_and(|(name, _)| is_valid_name(name))
+        })
+        .count();
+    match words.len() == skipped {
+        true => (start, Kind::Command),
+        false => (start, Kind::Argument(words.split_off(skipped))),
+    }
+}
+
+/// Returns the word without the quotes and the backslashes escaping characters
+fn unquote(chars: &[char]) -> String {
+    let mut word = String::new();
+    let mut quote = None;
+    let mut iter = chars.iter();
+    while let Some(&ch) = iter.next() {
+        match (ch, quote) {
+            ('\\', None) => word.extend(iter.next()),
+            ('\\', Some('"')) => match iter.as_slice().first() {
+                Some(&next @ ('$' | '`' | '"' | '\\')) => {
+                    word.push(next);
+                    iter.next();
+                },
+                _ => word.push(ch),
+            },
+            ('\'' | '"', None) => quote = Some(ch),
+            (_, Some(q)) if ch == q => quote = None,
+            _ => word.push(ch),
+        }
+    }
+    word
+}
+
+/// Returns the names of the aliases, the builtins, the functions and the executables in `$PATH`
+/// beginning with the prefix
+fn complete_commands(shell: &Shell, prefix: &str) -> Vec<String> {
+    let mut names: Vec<String> = BUILTIN_NAMES.iter()
+        .map(|name| name.to_string())
+        .chain(shell.builtins.keys().cloned())
+        .chain(shell.aliases.keys().cloned())
+        .chain(shell.functions.keys().cloned())
+        .filter(|name| name.starts_with(prefix))
+        .collect();
+
+    for dir in shell.vars.get("PATH").unwrap_or_default().split(':').filter(|dir| !dir.is_empty()) {
+        let Ok(entries) = fs::read_dir(shell.path(dir)) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if name.starts_with(prefix) && is_executable(&entry.path()) {
+                names.push(name);
+            }
+        }
+    }
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+/// Returns the paths beginning with the prefix, where directories end with `/`
+///
+/// Only the directories and the executables are returned if `executable_only`,
+/// and the hidden files are returned only if the last component of the prefix begins with `.`.
+fn complete_paths(shell: &Shell, prefix: &str, executable_only: bool) -> Vec<String> {
+    let (dir, name_prefix) = match prefix.rfind('/') {
+        Some(i) => prefix.split_at(i + 1),
+        None => ("", prefix),
+    };
+    let Ok(entries) = fs::read_dir(shell.cwd.join(dir)) else {
+        return vec![];
+    };
+
+    let mut paths: Vec<String> = entries.flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(name_prefix) || (name.starts_with('.') && !name_prefix.starts_with('.')) {
+                return None;
+            }
+            let path = format!("{dir}{name}");
+            let full_path = shell.path(&path);
+            if full_path.is_dir() {
+                Some(path + "/")
+            } else {
+                (!executable_only || is_executable(&full_path)).then_some(path)
+            }
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Returns `$NAME` for the variables whose names begin with the prefix, or `${NAME}` if it begins with `{`
+fn complete_variables(shell: &Shell, prefix: &str) -> Vec<String> {
+    let (braced, prefix) = match prefix.strip_prefix('{') {
+        Some(prefix) => (true, prefix),
+        None => (false, prefix),
+    };
+    shell.vars.iter()
+        .filter(|(name, _)| name.starts_with(prefix))
+        .map(|(name, _)| if braced { format!("${{{name}}}") } else { format!("${name}") })
+        .collect()
+}
+
+/// Returns the candidates given by `complete` for the command, or `None` if it is not set for the command
+///
+/// The function is called with `COMP_WORDS`, `COMP_CWORD`, `COMP_LINE` and `COMP_POINT` set like Bash.
+fn complete_with_spec(shell: &mut Shell, words: &[String], word: &str, line: &str, cursor: usize)
+    -> Option<Vec<String>> {
+    let candidates = match shell.completions.get(&words[0])? {
+        CompletionSpec::Words(list) => list.split_whitespace()
+            .filter(|candidate| candidate.starts_with(word))
+            .map(str::to_string)
+            .collect(),
+        CompletionSpec::Function(func) => {
+            let func = func.clone();
+            let mut comp_words = words.to_vec();
+            comp_words.push(word.to_string());
+            let prev = words.last().cloned().unwrap_or_default();
+
+            shell.vars.set_array("COMP_WORDS", comp_words);
+            shell.vars.set("COMP_CWORD", words.len().to_string());
+            shell.vars.set("COMP_LINE", line.to_string());
+            shell.vars.set("COMP_POINT", cursor.to_string());
+            shell.vars.unset("COMPREPLY");
+
+            // The function leaves `$?` as it was, since it isn't run by the user.
+            let last_status = shell.last_status;
+            let args = [func.clone(), words[0].clone(), word.to_string(), prev];
+            if let Some(Err(err)) = call_function_by_name(shell, &args) {
+                shell.print_error(format_args!("\r\nshell: {func}: {err}"));
+            }
+            shell.flow = None;
+            shell.last_status = last_status;
+            // a plain variable is taken as the candidates separated by blanks
+            let reply = shell.vars.get_elements("COMPREPLY");
+            match reply.as_slice() {
+                [value] => value.split_whitespace().map(str::to_string).collect(),
+                _ => reply,
+            }
+        },
+    };
+    Some(candidates)
+}
@@ -0,0 +1,258 @@
+use nix::unistd::{access, getegid, geteuid, AccessFlags};
+use std::fs::{self, Metadata};
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::arith;
+use crate::expand::{expand_pattern, expand_regex, expand_word};
+use crate::glob;
+use crate::regex::Regex;
+use crate::shell::Shell;
+use crate::types::command::CondExpr;
+use crate::types::error::ShellError;
+
+const UNARY_OPS: [&str; 24] = [
+    "-a", "-b", "-c", "-d", "-e", "-f", "-g", "-h", "-k", "-p", "-r", "-s", "-t", "-u", "-w", "-x", "-G", "-L", "-N",
+    "-O", "-S", "-n", "-z", "-v",
+];
+
+const BINARY_OPS: [&str; 14] = [
+    "=", "==", "!=", "<", ">", "-eq", "-ne", "-lt", "-le", "-gt", "-ge", "-nt", "-ot", "-ef",
+];
+
+/// Whether it is an operator taking one operand, e.g., `-f`
+pub fn is_unary_op(op: &str) -> bool {
+    UNARY_OPS.contains(&op)
+}
+
+/// Whether it is an operator between two operands except `=~`, which only `[[ ]]` supports
+pub fn is_binary_op(op: &str) -> bool {
+    BINARY_OPS.contains(&op)
+}
+
+/// Evaluates the expression of `[[ ]]`
+///
+/// `BASH_REMATCH` is set to the match and the groups of the regular expression after `=~`.
+pub fn eval(shell: &mut Shell, expr: &CondExpr) -> Result<bool, ShellError> {
+    match expr {
+        CondExpr::Not(expr) => Ok(!eval(shell, expr)?),
+        CondExpr::And(lhs, rhs) => Ok(eval(shell, lhs)? && eval(shell, rhs)?),
+        CondExpr::Or(lhs, rhs) => Ok(eval(shell, lhs)? || eval(shell, rhs)?),
+        CondExpr::Word(word) => Ok(!expand_word(shell, word)?.is_empty()),
+        CondExpr::Unary { op, arg } => {
+            let arg = expand_word(shell, arg)?;
+            Ok(unary_test(shell, op, &arg))
+        },
+        CondExpr::Binary { op, lhs, rhs } => {
+            let lhs = expand_word(shell, lhs)?;
+            match op.as_str() {
+                "=" | "==" | "!=" => {
+                    let pattern = expand_pattern(shell, rhs)?;
+                    Ok(glob::matches(&pattern, &lhs) == (op != "!="))
+                },
+                "=~" => {
+                    let pattern = expand_regex(shell, rhs)?;
+                    let regex = Regex::new(&pattern).map_err(|err| ShellError::Usage(format!("{pattern}: {err}")))?;
+                    let groups = regex.captures(&lhs);
+                    let matched = groups.is_some();
+                    shell.vars.set_array("BASH_REMATCH", groups.unwrap_or_default());
+                    Ok(matched)
+                },
+                // the operands are arithmetic expressions
+                "-eq" | "-ne" | "-lt" | "-le" | "-gt" | "-ge" => {
+                    let lhs = arith::eval(shell, &lhs)?;
+                    let rhs = expand_word(shell, rhs)?;
+                    let rhs = arith::eval(shell, &rhs)?;
+                    Ok(compare(op, lhs, rhs))
+                },
+                _ => {
+                    let rhs = expand_word(shell, rhs)?;
+                    binary_test(shell, op, &lhs, &rhs)
+                },
+            }
+        },
+    }
+}
+
+/// Evaluates the arguments of `test` or `[` without the closing `]`
+pub fn test(shell: &Shell, args: &[String]) -> Result<bool, ShellError> {
+    let mut parser = TestParser { shell, args, pos: 0 };
+    if args.is_empty() {
+        return Ok(false);
+    }
+    let value = parser.parse_or()?;
+    match args.len() {
+        _ if parser.pos == args.len() => Ok(value),
+        2 => Err(ShellError::Usage(format!("{}: unary operator expected", args[0]))),
+        3 => Err(ShellError::Usage(format!("{}: binary operator expected", args[1]))),
+        _ => Err(ShellError::Usage("too many arguments".to_string())),
+    }
+}
+
+/// A recursive descent parser evaluating the arguments of `test`,
+/// where `-o` has lower precedence than `-a`, which has lower precedence than `!`
+struct TestParser<'a> {
+    shell: &'a Shell,
+    args: &'a [String],
+    pos: usize,
+}
+
+impl TestParser<'_> {
+    fn peek(&self) -> Option<&str> {
+        self.args.get(self.pos).map(String::as_str)
+    }
+
+    /// Whether there are `n` more arguments after the current one
+    fn has_more(&self, n: usize) -> bool {
+        self.pos + n < self.args.len()
+    }
+
+    fn parse_or(&mut self) -> Result<bool, ShellError> {
+        let mut value = self.parse_and()?;
+        while self.peek() == Some("-o") && self.has_more(1) {
+            self.pos += 1;
+            let rhs = self.parse_and()?;
+            value = value || rhs;
+        }
+        Ok(value)
+    }
+
+    fn parse_and(&mut self) -> Result<bool, ShellError> {
+        let mut value = self.parse_not()?;
+        while self.peek() == Some("-a") && self.has_more(1) {
+            self.pos += 1;
+            let rhs = self.parse_not()?;
+            value = value && rhs;
+        }
+        Ok(value)
+    }
+
+    fn parse_not(&mut self) -> Result<bool, ShellError> {
+        if self.peek() == Some("!") && self.has_more(1) && !self.at_binary() {
+            self.pos += 1;
+            return Ok(!self.parse_not()?);
+        }
+        self.parse_primary()
+    }
+
+    /// Whether the current argument is followed by a binary operator and its operand,
+    /// which takes precedence over the other meanings of the argument like `! = x`
+    fn at_binary(&self) -> bool {
+        self.has_more(2) && is_binary_op(&self.args[self.pos + 1])
+    }
+
+    fn parse_primary(&mut self) -> Result<bool, ShellError> {
+        let args = self.args;
+        let Some(arg) = args.get(self.pos).map(String::as_str) else {
+            return Err(ShellError::Usage("argument expected".to_string()));
+        };
+
+        if self.at_binary() {
+            let (lhs, op, rhs) = (arg, &args[self.pos + 1], &args[self.pos + 2]);
+            self.pos += 3;
+            return match op.as_str() {
+                "-eq" | "-ne" | "-lt" | "-le" | "-gt" | "-ge" => Ok(compare(op, parse_int(lhs)?, parse_int(rhs)?)),
+                _ => binary_test(self.shell, op, lhs, rhs),
+            };
+        }
+        if arg == "(" && self.has_more(1) {
+            self.pos += 1;
+            let value = self.parse_or()?;
+            if self.peek() != Some(")") {
+                return Err(ShellError::Usage("`)` expected".to_string()));
+            }
+            self.pos += 1;
+            return Ok(value);
+        }
+        if is_unary_op(arg) && self.has_more(1) {
+            let operand = &args[self.pos + 1];
+            self.pos += 2;
+            return Ok(unary_test(self.shell, arg, operand));
+        }
+        // a single argument is true if it is non-empty
+        self.pos += 1;
+        Ok(!arg.is_empty())
+    }
+}
+
+/// Parses an operand of an integer comparison, which may be surrounded by blanks
+fn parse_int(s: &str) -> Result<i64, ShellError> {
+    s.trim().parse().map_err(|_| ShellError::Usage(format!("{s}: integer expression expected")))
+}
+
+fn compare(op: &str, lhs: i64, rhs: i64) -> bool {
+    match op {
+        "-eq" => lhs == rhs,
+        "-ne" => lhs != rhs,
+        "-lt" => lhs < rhs,
+        "-le" => lhs <= rhs,
+        "-gt" => lhs > rhs,
+        _ => lhs >= rhs,
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Tests the operand with an operator like `-f`, where a file that cannot be accessed makes the test false
+fn unary_test(shell: &Shell, op: &str, arg: &str) -> bool {
+    let path = shell.path(arg);
+    let meta = || fs::metadata(&path);
+    let has = |test: fn(&Metadata) -> bool| meta().is_ok_and(|meta| test(&meta));
+
+    match op {
+        "-n" => !arg.is_empty(),
+        "-z" => arg.is_empty(),
+        "-v" => shell.vars.get(arg).is_some(),
+        "-a" | "-e" => meta().is_ok(),
+        "-f" => has(Metadata::is_file),
+        "-d" => has(Metadata::is_dir),
+        "-b" => has(|meta| meta.file_type().is_block_device()),
+        "-c" => has(|meta| meta.file_type().is_char_device()),
+        "-p" => has(|meta| meta.file_type().is_fifo()),
+        "-S" => has(|meta| meta.file_type().is_socket()),
+        "-h" | "-L" => fs::symlink_metadata(&path).is_ok_and(|meta| meta.file_type().is_symlink()),
+        "-s" => has(|meta| meta.len() > 0),
+        "-g" => has(|meta| meta.mode() & 0o2000 != 0),
+        "-u" => has(|meta| meta.mode() & 0o4000 != 0),
+        "-k" => has(|meta| meta.mode() & 0o1000 != 0),
+        "-O" => has(|meta| meta.uid() == geteuid().as_raw()),
+        "-G" => has(|meta| meta.gid() == getegid().as_raw()),
+        // modified since it was last read
+        "-N" => has(|meta| matches!((meta.modified(), meta.accessed()), (Ok(mtime), Ok(atime)) if mtime > atime)),
+        "-r" => access(&path, AccessFlags::R_OK).is_ok(),
+        "-w" => access(&path, AccessFlags::W_OK).is_ok(),
+        "-x" => access(&path, AccessFlags::X_OK).is_ok(),
+        "-t" => arg.trim().parse().ok()
+            .and_then(|fd| shell.raw_fd(fd))
+            .is_some_and(|fd| unsafe { libc::isatty(fd) } == 1),
+        _ => false,
+    }
+}
+
+/// Tests the operands with an operator other than the integer comparisons
+fn binary_test(shell: &Shell, op: &str, lhs: &str, rhs: &str) -> Result<bool, ShellError> {
+    Ok(match op {
+        "=" | "==" => lhs == rhs,
+        "!=" => lhs != rhs,
+        "<" => lhs < rhs,
+        ">" => lhs > rhs,
+        // A file that exists is newer than the one that doesn't.
+        "-nt" => match (modified(&shell.path(lhs)), modified(&shell.path(rhs))) {
+            (Some(lhs), Some(rhs)) => lhs > rhs,
+            (lhs, rhs) => lhs.is_some() && rhs.is_none(),
+        },
+        "-ot" => match (modified(&shell.path(lhs)), modified(&shell.path(rhs))) {
+            (Some(lhs), Some(rhs)) => lhs < rhs,
+            (lhs, rhs) => lhs.is_none() && rhs.is_some(),
+        },
+        // the same device and inode
+        "-ef" => match (fs::metadata(shell.path(lhs)), fs::metadata(shell.path(rhs))) {
+            (Ok(lhs), Ok(rhs)) => lhs.dev() == rhs.dev() && lhs.ino() == rhs.ino(),
+            _ => false,
+        },
+        _ => return Err(ShellError::Usage(format!("{op}: binary operator expected"))),
+    })
+}
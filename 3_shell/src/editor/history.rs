@@ -0,0 +1,113 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// The commands entered so far from the oldest, which are kept in a file across sessions
+///
+/// Each command appears only once, and the lines of a multi-line command are joined by backslashes in the file,
+/// where the backslashes in the command are doubled.
+pub struct History {
+    entries: Vec<String>,
+    path: Option<PathBuf>,
+    /// the maximum number of entries
+    size: usize,
+}
+
+impl History {
+    /// Loads the history from the file if any, which is rewritten if the duplicates or the oldest entries are dropped
+    pub fn load(path: Option<PathBuf>, size: usize) -> Self {
+        let mut history = Self { entries: vec![], path, size };
+        let Some(text) = history.path.as_ref().and_then(|path| fs::read_to_string(path).ok()) else {
+            return history;
+        };
+
+        let mut count = 0;
+        let mut entry = String::new();
+        for line in text.lines() {
+            if decode(line, &mut entry) {
+                entry.push('\n');
+            } else {
+                history.push(std::mem::take(&mut entry));
+                count += 1;
+            }
+        }
+        if history.entries.len() != count {
+            history.save();
+        }
+        history
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(String::as_str)
+    }
+
+    /// Appends the command without the trailing newline unless it is blank,
+    /// and returns whether any entry is dropped
+    fn push(&mut self, entry: String) -> bool {
+        if entry.trim().is_empty() || self.size == 0 {
+            return false;
+        }
+        let len = self.entries.len();
+        self.entries.retain(|other| *other != entry);
+        self.entries.push(entry);
+        if self.entries.len() > self.size {
+            let excess = self.entries.len() - self.size;
+            self.entries.drain(..excess);
+        }
+        self.entries.len() <= len
+    }
+
+    /// Adds the command, which is also appended to the file
+    pub fn add(&mut self, entry: &str) {
+        let entry = entry.trim_end_matches('\n').to_string();
+        if self.push(entry.clone()) {
+            self.save();
+            return;
+        }
+        if self.entries.last() != Some(&entry) {
+            return;
+        }
+        if let Some(path) = &self.path
+            && let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+            let _ = file.write_all(encode(&entry).as_bytes());
+        }
+    }
+
+    /// Rewrites the file with all the entries
+    fn save(&self) {
+        if let Some(path) = &self.path {
+            let text: String = self.entries.iter().map(|entry| encode(entry)).collect();
+            let _ = fs::write(path, text);
+        }
+    }
+}
+
+/// Returns the line of the entry in the file
+fn encode(entry: &str) -> String {
+    format!("{}\n", entry.replace('\\', "\\\\").replace('\n', "\\\n"))
+}
+
+/// Appends the line in the file to the entry, and returns whether the entry continues on the next line
+fn decode(line: &str, entry: &mut String) -> bool {
+    let mut chars = line.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            entry.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => entry.push('\\'),
+            // kept as is
+            Some(next) => {
+                entry.push(ch);
+                entry.push(next);
+            },
+            None => return true,
+        }
+    }
+    false
+}
@@ -0,0 +1,477 @@
+use nix::errno::Errno;
+use std::path::PathBuf;
+
+use crate::editor::history::History;
+use crate::editor::terminal::{Key, RawMode};
+use crate::prompt::{INVISIBLE_END, INVISIBLE_START};
+
+mod history;
+mod terminal;
+
+/// The text being edited and where the cursor is
+#[derive(Default)]
+struct Line {
+    buf: Vec<char>,
+    /// the index of the character under the cursor
+    pos: usize,
+    /// the row of the cursor counted from the one the prompt begins at when it is last drawn
+    cursor_row: usize,
+    /// the prompt shown before the lines following the first one of a multi-line text
+    continuation: String,
+}
+
+impl Line {
+    fn text(&self) -> String {
+        self.buf.iter().collect()
+    }
+
+    fn set_text(&mut self, text: &str) {
+        self.buf = text.chars().collect();
+        self.pos = self.buf.len();
+    }
+
+    fn insert(&mut self, text: &[char]) {
+        self.buf.splice(self.pos..self.pos, text.iter().copied());
+        self.pos += text.len();
+    }
+
+    /// Removes the characters in the range, and moves the cursor to where they were
+    fn remove(&mut self, start: usize, end: usize) -> String {
+        self.pos = start;
+        self.buf.drain(start..end).collect()
+    }
+
+    /// Where the line of the multi-line text the cursor is on begins
+    fn line_start(&self, pos: usize) -> usize {
+        self.buf[..pos].iter().rposition(|&ch| ch == '\n').map_or(0, |i| i + 1)
+    }
+
+    /// Where the line of the multi-line text the cursor is on ends, excluding the newline
+    fn line_end(&self, pos: usize) -> usize {
+        self.buf[pos..].iter().position(|&ch| ch == '\n').map_or(self.buf.len(), |i| pos + i)
+    }
+
+    /// Where the word before the cursor begins, where words consist of the characters satisfying `is_word`
+    fn word_start(&self, is_word: fn(char) -> bool) -> usize {
+        let mut i = self.pos;
+        while i > 0 && !is_word(self.buf[i - 1]) {
+            i -= 1;
+        }
+        while i > 0 && is_word(self.buf[i - 1]) {
+            i -= 1;
+        }
+        i
+    }
+
+    /// Where the word after the cursor ends
+    fn word_end(&self, is_word: fn(char) -> bool) -> usize {
+        let mut i = self.pos;
+        while i < self.buf.len() && !is_word(self.buf[i]) {
+            i += 1;
+        }
+        while i < self.buf.len() && is_word(self.buf[i]) {
+            i += 1;
+        }
+        i
+    }
+
+    /// Moves the cursor to the same column of the previous line of a multi-line text if any
+    fn move_up(&mut self) -> bool {
+        let start = self.line_start(self.pos);
+        if start == 0 {
+            return false;
+        }
+        let prev_start = self.line_start(start - 1);
+        self.pos = prev_start + (self.pos - start).min(start - 1 - prev_start);
+        true
+    }
+
+    /// Moves the cursor to the same column of the next line of a multi-line text if any
+    fn move_down(&mut self) -> bool {
+        let end = self.line_end(self.pos);
+        if end == self.buf.len() {
+            return false;
+        }
+        let col = self.pos - self.line_start(self.pos);
+        let next_end = self.line_end(end + 1);
+        self.pos = end + 1 + col.min(next_end - end - 1);
+        true
+    }
+
+    /// Redraws the prompt and the text, where the lines after a newline are preceded by the continuation prompt
+    /// and the long ones are wrapped at the width of the terminal
+    fn render(&mut self, prompt: &str) {
+        let width = terminal::width();
+        let mut out = String::new();
+        if self.cursor_row > 0 {
+            out.push_str(&format!("\x1b[{}A", self.cursor_row));
+        }
+        out.push_str("\r\x1b[J");
+
+        // The column may be equal to the width, where the terminal wraps before the next character.
+        let (mut row, mut col) = (0, 0);
+        let advance = |ch: char, row: &mut usize, col: &mut usize| {
+            if ch == '\n' {
+                *row += 1;
+                *col = 0;
+                return;
+            }
+            if *col == width {
+                *row += 1;
+                *col = 0;
+            }
+            *col += 1;
+        };
+        let wrap = |(row, col): (usize, usize)| if col == width { (row + 1, 0) } else { (row, col) };
+        // The characters between `INVISIBLE_START` and `INVISIBLE_END` take no space.
+        let draw_prompt = |prompt: &str, out: &mut String, row: &mut usize, col: &mut usize| {
+            let mut visible = true;
+            for ch in prompt.chars() {
+                match ch {
+                    INVISIBLE_START => visible = false,
+                    INVISIBLE_END => visible = true,
+                    '\n' => {
+                        out.push_str("\r\n");
+                        advance(ch, row, col);
+                    },
+                    _ => {
+                        out.push(ch);
+                        if visible {
+                            advance(ch, row, col);
+                        }
+                    },
+                }
+            }
+        };
+
+        draw_prompt(prompt, &mut out, &mut row, &mut col);
+        let mut cursor = (row, col);
+        for (i, &ch) in self.buf.iter().enumerate() {
+            if i == self.pos {
+                cursor = wrap((row, col));
+            }
+            if ch == '\n' {
+                out.push_str("\r\n");
+                advance(ch, &mut row, &mut col);
+                draw_prompt(&self.continuation, &mut out, &mut row, &mut col);
+            } else {
+                out.push(ch);
+                advance(ch, &mut row, &mut col);
+            }
+        }
+        if self.pos == self.buf.len() {
+            cursor = wrap((row, col));
+        }
+        // move to the next row for real if the last character reaches the right edge
+        if col == width {
+            out.push_str("\r\n");
+            row += 1;
+        }
+
+        if row > cursor.0 {
+            out.push_str(&format!("\x1b[{}A", row - cursor.0));
+        }
+        out.push('\r');
+        if cursor.1 > 0 {
+            out.push_str(&format!("\x1b[{}C", cursor.1));
+        }
+        self.cursor_row = cursor.0;
+        terminal::write(&out);
+    }
+
+    /// Moves the cursor past the end of the text, so that the output goes below it
+    fn finish(&mut self, prompt: &str, suffix: &str) {
+        self.pos = self.buf.len();
+        self.render(prompt);
+        terminal::write(suffix);
+    }
+}
+
+fn is_alphanumeric(ch: char) -> bool {
+    ch.is_alphanumeric()
+}
+
+fn is_not_whitespace(ch: char) -> bool {
+    !ch.is_whitespace()
+}
+
+/// The words that may replace the one before the cursor
+pub struct Completion {
+    /// where the word begins
+    pub start: usize,
+    /// the candidates, where a directory ends with `/` and the others are followed by a space once chosen
+    pub candidates: Vec<String>,
+}
+
+/// Returns how a candidate is listed, e.g., only the last component of a path
+fn display_name(candidate: &str) -> &str {
+    let trimmed = candidate.strip_suffix('/').unwrap_or(candidate);
+    match trimmed.rfind('/') {
+        Some(i) => &candidate[i + 1..],
+        None => candidate,
+    }
+}
+
+/// How the reverse search ends
+enum SearchEnd {
+    /// with Ctrl-G, which restores the line before the search
+    Cancel,
+    /// with a key other than those editing the query, which is then handled with the found line
+    Key(Key),
+}
+
+/// A line editor with Emacs-like key bindings and the history of the commands
+pub struct Editor {
+    history: History,
+    /// the text killed last, which is inserted by Ctrl-Y
+    kill_buffer: Vec<char>,
+}
+
+impl Editor {
+    /// Loads at most `history_size` commands from the history file
+    pub fn new(history_path: Option<PathBuf>, history_size: usize) -> Self {
+        Self { history: History::load(history_path, history_size), kill_buffer: vec![] }
+    }
+
+    /// Adds the command to the history
+    pub fn add_history(&mut self, command: &str) {
+        self.history.add(command);
+    }
+
+    /// Reads a command with the trailing newline, or returns `None` on EOF, i.e., Ctrl-D on an empty line
+    ///
+    /// The lines following the first one of a multi-line command are preceded by `continuation` instead of `prompt`.
+    /// Enter inserts a newline rather than accepting the command if `is_complete` is false for the text,
+    /// and `Errno::EINTR` is returned if the command is cancelled by Ctrl-C.
+    ///
+    /// Tab completes the word before the cursor with the candidates given by `complete` for the text and the cursor.
+    pub fn read_line(
+        &mut self,
+        prompt: &str,
+        continuation: &str,
+        is_complete: &dyn Fn(&str) -> bool,
+        complete: &mut dyn FnMut(&str, usize) -> Completion,
+    ) -> Result<Option<String>, Errno> {
+        let _raw_mode = RawMode::enable()?;
+        let mut line = Line { continuation: continuation.to_string(), ..Line::default() };
+        // the index of the history entry shown, where `history.len()` is for the text being edited
+        let mut index = self.history.len();
+        let mut edited = String::new();
+        let mut pending = None;
+
+        loop {
+            let key = match pending.take() {
+                Some(key) => key,
+                None => {
+                    line.render(prompt);
+                    match terminal::read_key() {
+                        Ok(Some(key)) => key,
+                        Ok(None) if line.buf.is_empty() => return Ok(None),
+                        // the command is accepted as is if the terminal is gone
+                        Ok(None) => Key::Enter,
+                        Err(Errno::EINTR) => Key::Ctrl('c'),
+                        Err(err) => return Err(err),
+                    }
+                },
+            };
+
+            match key {
+                Key::Enter => {
+                    let text = line.text();
+                    if !is_complete(&text) {
+                        line.insert(&['\n']);
+                        continue;
+                    }
+                    line.finish(prompt, "\r\n");
+                    return Ok(Some(text + "\n"));
+                },
+                Key::Ctrl('c') => {
+                    line.finish(prompt, "^C");
+                    return Err(Errno::EINTR);
+                },
+                Key::Ctrl('d') if line.buf.is_empty() => {
+                    terminal::write("\r\n");
+                    return Ok(None);
+                },
+                Key::Char(ch) => line.insert(&[ch]),
+                Key::Backspace if line.pos > 0 => {
+                    line.remove(line.pos - 1, line.pos);
+                },
+                Key::Delete | Key::Ctrl('d') if line.pos < line.buf.len() => {
+                    line.remove(line.pos, line.pos + 1);
+                },
+                Key::Left | Key::Ctrl('b') => line.pos = line.pos.saturating_sub(1),
+                Key::Right | Key::Ctrl('f') => line.pos = (line.pos + 1).min(line.buf.len()),
+                Key::Home | Key::Ctrl('a') => line.pos = line.line_start(line.pos),
+                Key::End | Key::Ctrl('e') => line.pos = line.line_end(line.pos),
+                Key::CtrlLeft | Key::Alt('b') => line.pos = line.word_start(is_alphanumeric),
+                Key::CtrlRight | Key::Alt('f') => line.pos = line.word_end(is_alphanumeric),
+                Key::Ctrl('k') => {
+                    let range = (line.pos, line.line_end(line.pos));
+                    self.kill(&mut line, range);
+                },
+                Key::Ctrl('u') => {
+                    let range = (line.line_start(line.pos), line.pos);
+                    self.kill(&mut line, range);
+                },
+                Key::Ctrl('w') => {
+                    let range = (line.word_start(is_not_whitespace), line.pos);
+                    self.kill(&mut line, range);
+                },
+                Key::AltBackspace => {
+                    let range = (line.word_start(is_alphanumeric), line.pos);
+                    self.kill(&mut line, range);
+                },
+                Key::Alt('d') => {
+                    let range = (line.pos, line.word_end(is_alphanumeric));
+                    self.kill(&mut line, range);
+                },
+                Key::Ctrl('y') => line.insert(&self.kill_buffer.clone()),
+                // The cursor moves between the lines of a multi-line text before going through the history.
+                Key::Up | Key::Ctrl('p') => {
+                    if line.move_up() || index == 0 {
+                        continue;
+                    }
+                    if index == self.history.len() {
+                        edited = line.text();
+                    }
+                    index -= 1;
+                    line.set_text(self.history.get(index).unwrap_or_default());
+                },
+                Key::Down | Key::Ctrl('n') => {
+                    if line.move_down() || index == self.history.len() {
+                        continue;
+                    }
+                    index += 1;
+                    match self.history.get(index) {
+                        Some(entry) => line.set_text(entry),
+                        None => line.set_text(&edited),
+                    }
+                },
+                Key::Ctrl('r') => {
+                    if index == self.history.len() {
+                        edited = line.text();
+                    }
+                    match self.search(&mut line, &mut index)? {
+                        SearchEnd::Key(key) => pending = Some(key),
+                        SearchEnd::Cancel => {},
+                    }
+                },
+                Key::Tab => {
+                    let completion = complete(&line.text(), line.pos);
+                    self.complete(&mut line, prompt, completion);
+                },
+                Key::Ctrl('l') => {
+                    terminal::write("\x1b[H\x1b[2J");
+                    line.cursor_row = 0;
+                },
+                _ => {},
+            }
+        }
+    }
+
+    /// Replaces the word with the only candidate, or the longest prefix shared by the candidates,
+    /// or lists the candidates in columns below the line if the word can't be longer
+    fn complete(&mut self, line: &mut Line, prompt: &str, completion: Completion) {
+        let Completion { start, candidates } = completion;
+        let word_len = line.pos - start;
+        let common = match candidates.as_slice() {
+            [] => {
+                terminal::write("\x07");
+                return;
+            },
+            [candidate] if candidate.ends_with('/') => candidate.clone(),
+            [candidate] => format!("{candidate} "),
+            [first, rest @ ..] => rest.iter().fold(first.clone(), |common, candidate| {
+                common.chars().zip(candidate.chars()).take_while(|(a, b)| a == b).map(|(a, _)| a).collect()
+            }),
+        };
+        if common.chars().count() > word_len {
+            line.remove(start, line.pos);
+            line.insert(&common.chars().collect::<Vec<_>>());
+            return;
+        }
+
+        // listed row by row in as many columns as the terminal can hold
+        let names: Vec<&str> = candidates.iter().map(|candidate| display_name(candidate)).collect();
+        let col_width = names.iter().map(|name| name.chars().count()).max().unwrap_or(0) + 2;
+        let cols = (terminal::width() / col_width).max(1);
+        let rows = names.len().div_ceil(cols);
+        let mut out = String::new();
+        for row in 0..rows {
+            for col in 0..cols {
+                // column-major like `ls`
+                if let Some(name) = names.get(col * rows + row) {
+                    out.push_str(&format!("{name:<col_width$}"));
+                }
+            }
+            out = out.trim_end().to_string();
+            out.push_str("\r\n");
+        }
+        let pos = line.pos;
+        line.finish(prompt, "\r\n");
+        terminal::write(&out);
+        line.pos = pos;
+        line.cursor_row = 0;
+    }
+
+    /// Removes the text in the range, which can be inserted back by Ctrl-Y
+    fn kill(&mut self, line: &mut Line, (start, end): (usize, usize)) {
+        if start < end {
+            self.kill_buffer = line.remove(start, end).chars().collect();
+        }
+    }
+
+    /// Searches the history backward incrementally for the entry containing the query typed,
+    /// where Ctrl-R again finds the older one
+    fn search(&mut self, line: &mut Line, index: &mut usize) -> Result<SearchEnd, Errno> {
+        let (orig_text, orig_pos, orig_index) = (line.text(), line.pos, *index);
+        let mut query = String::new();
+        let mut failed = false;
+
+        loop {
+            let prompt = format!("({}reverse-i-search)`{query}': ", if failed { "failed " } else { "" });
+            line.render(&prompt);
+
+            // the entries before `upper` are searched from the newest
+            let key = terminal::read_key()?.unwrap_or(Key::Ctrl('g'));
+            let upper = match key {
+                Key::Char(ch) => {
+                    query.push(ch);
+                    // the current entry may still contain the longer query
+                    (*index + 1).min(self.history.len())
+                },
+                Key::Backspace => {
+                    query.pop();
+                    orig_index
+                },
+                Key::Ctrl('r') => *index,
+                Key::Ctrl('g') => {
+                    line.set_text(&orig_text);
+                    line.pos = orig_pos;
+                    *index = orig_index;
+                    return Ok(SearchEnd::Cancel);
+                },
+                key => return Ok(SearchEnd::Key(key)),
+            };
+            if query.is_empty() {
+                failed = false;
+                continue;
+            }
+
+            let found = (0..upper).rev().find_map(|i| {
+                let entry = self.history.get(i)?;
+                entry.rfind(&query).map(|offset| (i, entry, entry[..offset].chars().count()))
+            });
+            match found {
+                Some((i, entry, pos)) => {
+                    line.set_text(entry);
+                    line.pos = pos;
+                    *index = i;
+                    failed = false;
+                },
+                None => failed = true,
+            }
+        }
+    }
+}
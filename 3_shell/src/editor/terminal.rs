@@ -0,0 +1,154 @@
+use nix::errno::Errno;
+use nix::sys::termios::{tcgetattr, tcsetattr, InputFlags, LocalFlags, SetArg, SpecialCharacterIndices, Termios};
+use nix::unistd::read;
+use std::io::{self, Write};
+
+/// Puts the terminal into raw mode, where the keys are read one by one without being echoed,
+/// and restores it when dropped
+pub struct RawMode {
+    original: Termios,
+}
+
+impl RawMode {
+    pub fn enable() -> Result<Self, Errno> {
+        let original = tcgetattr(io::stdin())?;
+        let mut raw = original.clone();
+        // Ctrl-C and the like are read as keys rather than sending signals.
+        raw.local_flags.remove(LocalFlags::ICANON | LocalFlags::ECHO | LocalFlags::ISIG | LocalFlags::IEXTEN);
+        raw.input_flags.remove(InputFlags::ICRNL | InputFlags::IXON | InputFlags::BRKINT | InputFlags::ISTRIP);
+        raw.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
+        raw.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
+        tcsetattr(io::stdin(), SetArg::TCSADRAIN, &raw)?;
+        Ok(Self { original })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = tcsetattr(io::stdin(), SetArg::TCSADRAIN, &self.original);
+    }
+}
+
+/// Returns the number of columns of the terminal, which defaults to 80
+pub fn width() -> usize {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let res = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    match res == 0 && size.ws_col > 0 {
+        true => size.ws_col as usize,
+        false => 80,
+    }
+}
+
+/// Writes the escape sequences and the text to the terminal at once
+pub fn write(s: &str) {
+    let mut stdout = io::stdout();
+    let _ = stdout.write_all(s.as_bytes());
+    let _ = stdout.flush();
+}
+
+pub enum Key {
+    Char(char),
+    /// Ctrl with a lowercase letter, e.g., `Ctrl('a')` for Ctrl-A
+    Ctrl(char),
+    /// Alt or Esc followed by a character, e.g., `Alt('b')` for Alt-B
+    Alt(char),
+    Enter,
+    Tab,
+    Backspace,
+    /// Alt-Backspace
+    AltBackspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    CtrlLeft,
+    CtrlRight,
+    /// an escape sequence not bound to anything
+    Unknown,
+}
+
+fn read_byte() -> Result<Option<u8>, Errno> {
+    let mut byte = [0u8];
+    match read(io::stdin(), &mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+/// Reads a key, which is `None` on EOF
+pub fn read_key() -> Result<Option<Key>, Errno> {
+    let Some(byte) = read_byte()? else {
+        return Ok(None);
+    };
+    let key = match byte {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7f | 0x08 => Key::Backspace,
+        0x1b => read_escape()?,
+        0x01..=0x1a => Key::Ctrl((b'a' + byte - 1) as char),
+        0x20..=0x7e => Key::Char(byte as char),
+        0x00..=0x1f => Key::Unknown,
+        _ => read_utf8(byte)?,
+    };
+    Ok(Some(key))
+}
+
+/// Reads the rest of a multibyte character
+fn read_utf8(first: u8) -> Result<Key, Errno> {
+    let len = match first {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return Ok(Key::Unknown),
+    };
+    let mut bytes = vec![first];
+    for _ in 1..len {
+        match read_byte()? {
+            Some(byte) => bytes.push(byte),
+            None => break,
+        }
+    }
+    Ok(std::str::from_utf8(&bytes).ok()
+        .and_then(|s| s.chars().next())
+        .map_or(Key::Unknown, Key::Char))
+}
+
+/// Reads the sequence following Esc, e.g., `[A` for the up arrow
+fn read_escape() -> Result<Key, Errno> {
+    let Some(byte) = read_byte()? else {
+        return Ok(Key::Unknown);
+    };
+    if byte != b'[' && byte != b'O' {
+        return Ok(match byte {
+            0x7f | 0x08 => Key::AltBackspace,
+            0x20..=0x7e => Key::Alt(byte.to_ascii_lowercase() as char),
+            _ => Key::Unknown,
+        });
+    }
+
+    // the parameters up to the final byte, e.g., `1;5` in `[1;5C`
+    let mut params = String::new();
+    let final_byte = loop {
+        match read_byte()? {
+            Some(byte @ 0x40..=0x7e) => break byte,
+            Some(byte) => params.push(byte as char),
+            None => return Ok(Key::Unknown),
+        }
+    };
+    let ctrl = params.ends_with(";5");
+    Ok(match (final_byte, params.as_str()) {
+        (b'A', _) => Key::Up,
+        (b'B', _) => Key::Down,
+        (b'C', _) if ctrl => Key::CtrlRight,
+        (b'D', _) if ctrl => Key::CtrlLeft,
+        (b'C', _) => Key::Right,
+        (b'D', _) => Key::Left,
+        (b'H', _) | (b'~', "1" | "7") => Key::Home,
+        (b'F', _) | (b'~', "4" | "8") => Key::End,
+        (b'~', "3") => Key::Delete,
+        _ => Key::Unknown,
+    })
+}
@@ -1,30 +1,595 @@
-use std::env;
-use std::path::PathBuf;
+use nix::unistd::Pid;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
-use crate::types::error::GenericError;
+use crate::alias;
+use crate::complete::CompletionSpec;
+use crate::cond;
+use crate::executor::jobs::{set_foreground, JobState};
+use crate::parser::quote;
+use crate::repl;
+use crate::shell::{Flow, SetOptions, Shell, Shopts};
+use crate::types::error::ShellError;
+use crate::types::word::is_valid_name;
 
-pub fn echo(args: &Vec<String>) -> Result<(), GenericError> {
-    println!("{}", args[1..].join(" "));
-    Ok(())
+pub fn echo(shell: &mut Shell, args: &[String]) -> Result<i32, ShellError> {
+    writeln!(shell.stdout(), "{}", args[1..].join(" "))?;
+    Ok(0)
+}
+
+/// Changes the directory to `DIR` or `$HOME`, and updates `$PWD` and `$OLDPWD`
+pub fn cd(shell: &mut Shell, args: &[String]) -> Result<i32, ShellError> {
+    let path = match args.len() {
+        1 => match shell.vars.get("HOME") {
+            Some(home) => PathBuf::from(home),
+            None => return Err(ShellError::Builtin("HOME not set".to_string())),
+        },
+        2 => PathBuf::from(&args[1]),
+        _ => return Err(ShellError::Builtin("too many arguments".to_string())),
+    };
+    shell.set_cwd(&path)?;
+    Ok(0)
+}
+
+pub fn pwd(shell: &mut Shell, args: &[String]) -> Result<i32, ShellError> {
+    if args.len() > 1 {
+        return Err(ShellError::Builtin("too many arguments".to_string()));
+    }
+    writeln!(shell.stdout(), "{}", shell.cwd.display())?;
+    Ok(0)
+}
+
+/// Returns the status to exit with, which defaults to the last exit status
+pub fn exit(shell: &mut Shell, args: &[String]) -> Result<i32, ShellError> {
+    exit_status(shell, args)
 }
 
-pub fn cd(args: &Vec<String>) -> Result<(), GenericError> {
-    let path;
+/// `return [N]` returns from the function or the sourced file with the status, which defaults to the last exit status
+pub fn r#return(shell: &mut Shell, args: &[String]) -> Result<i32, ShellError> {
+    if shell.func_depth == 0 && shell.source_depth == 0 {
+        return Err(ShellError::Builtin("can only `return` from a function or sourced script".to_string()));
+    }
+    let status = exit_status(shell, args)?;
+    shell.flow = Some(Flow::Return(status));
+    Ok(status)
+}
 
+/// Parses the status given to `exit` or `return`
+fn exit_status(shell: &Shell, args: &[String]) -> Result<i32, ShellError> {
     match args.len() {
-        #![allow(deprecated)]
-        1 => path = env::home_dir().unwrap(),
-        2 => path = PathBuf::from(&args[1]),
-        _ => return Err("too many arguments".into()),
+        1 => Ok(shell.last_status),
+        2 => match args[1].parse::<i32>() {
+            // only the lowest 8 bits are visible to the parent
+            Ok(n) => Ok(n & 0xff),
+            Err(_) => Err(ShellError::Usage(format!("{}: numeric argument required", args[1]))),
+        },
+        _ => Err(ShellError::Builtin("too many arguments".to_string())),
     }
-    env::set_current_dir(&path)?;
-    Ok(())
 }
 
-pub fn pwd(args: &Vec<String>) -> Result<(), GenericError> {
+/// Lists the jobs, and forgets the done ones after they are listed
+pub fn jobs(shell: &mut Shell, args: &[String]) -> Result<i32, ShellError> {
     if args.len() > 1 {
-        return Err("too many arguments".into());
+        return Err(ShellError::Builtin("too many arguments".to_string()));
+    }
+    shell.jobs.update();
+    for job in shell.jobs.iter() {
+        writeln!(shell.stdout(), "{}", shell.jobs.format(job))?;
+    }
+    shell.jobs.clean();
+    Ok(0)
+}
+
+/// Resumes the job in the foreground and waits for it
+pub fn fg(shell: &mut Shell, args: &[String]) -> Result<i32, ShellError> {
+    if !shell.job_control {
+        return Err(ShellError::Job("no job control".to_string()));
+    }
+    let id = match args.len() {
+        1 => shell.jobs.resolve("%+")?,
+        2 => shell.jobs.resolve(&args[1])?,
+        _ => return Err(ShellError::Builtin("too many arguments".to_string())),
+    };
+    writeln!(shell.stdout(), "{}", shell.jobs.get(id).unwrap().text)?;
+    let job = shell.jobs.get_mut(id).unwrap();
+    if let Some(pgid) = job.pgid {
+        set_foreground(pgid);
+    }
+    let res = job.resume();
+    let state = job.wait();
+    set_foreground(shell.pgid);
+    res?;
+
+    match state {
+        JobState::Done(status) => {
+            shell.jobs.remove(id);
+            Ok(status)
+        },
+        _ => {
+            // move to the next line of `^Z`
+            shell.print_error("");
+            shell.jobs.touch(id);
+            shell.notify_jobs(true);
+            Ok(128 + nix::sys::signal::Signal::SIGTSTP as i32)
+        },
+    }
+}
+
+/// Resumes the stopped jobs in the background
+pub fn bg(shell: &mut Shell, args: &[String]) -> Result<i32, ShellError> {
+    if !shell.job_control {
+        return Err(ShellError::Job("no job control".to_string()));
+    }
+    let ids = match args.len() {
+        1 => vec![shell.jobs.resolve("%+")?],
+        _ => args[1..].iter()
+            .map(|spec| shell.jobs.resolve(spec))
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+
+    for id in ids {
+        let job = shell.jobs.get_mut(id).unwrap();
+        if job.state() == JobState::Running {
+            shell.print_error(format_args!("shell: bg: job {id} already in background"));
+            continue;
+        }
+        job.resume()?;
+        shell.jobs.touch(id);
+        let text = &shell.jobs.get(id).unwrap().text;
+        writeln!(shell.stdout(), "[{id}]{} {text} &", shell.jobs.mark(id))?;
+    }
+    Ok(0)
+}
+
+/// Waits for the given jobs or processes, or all the running jobs if none is given,
+/// and returns the exit status of the last one
+pub fn wait(shell: &mut Shell, args: &[String]) -> Result<i32, ShellError> {
+    let mut status = 0;
+
+    if args.len() == 1 {
+        shell.jobs.update();
+        let ids: Vec<usize> = shell.jobs.iter()
+            .filter(|job| job.state() == JobState::Running)
+            .map(|job| job.id)
+            .collect();
+        for id in ids {
+            if shell.jobs.get_mut(id).unwrap().wait() == JobState::Running {
+                // interrupted
+                return Ok(128 + nix::sys::signal::Signal::SIGINT as i32);
+            }
+        }
+        shell.jobs.clean();
+        return Ok(0);
+    }
+
+    for spec in &args[1..] {
+        let id = if spec.starts_with('%') {
+            shell.jobs.resolve(spec)?
+        } else {
+            let pid = spec.parse::<i32>()
+                .map_err(|_| ShellError::Usage(format!("`{spec}': not a pid or valid job spec")))?;
+            match shell.jobs.find_by_pid(Pid::from_raw(pid)) {
+                Some(id) => id,
+                None => {
+                    shell.print_error(format_args!("shell: wait: pid {pid} is not a child of this shell"));
+                    status = 127;
+                    continue;
+                },
+            }
+        };
+        status = match shell.jobs.get_mut(id).unwrap().wait() {
+            JobState::Done(job_status) => {
+                shell.jobs.remove(id);
+                job_status
+            },
+            JobState::Stopped => 128 + nix::sys::signal::Signal::SIGTSTP as i32,
+            JobState::Running => return Ok(128 + nix::sys::signal::Signal::SIGINT as i32),
+        };
+    }
+    Ok(status)
+}
+
+/// `export [-n] [NAME[=VALUE]]...`
+///
+/// Lists the exported variables if no name is given. With `-n`, the names are unexported instead.
+pub fn export(shell: &mut Shell, args: &[String]) -> Result<i32, ShellError> {
+    let (unexport, names) = match args.get(1).map(String::as_str) {
+        Some("-n") => (true, &args[2..]),
+        Some("-p") => (false, &args[2..]),
+        _ => (false, &args[1..]),
+    };
+
+    if names.is_empty() {
+        for (name, var) in shell.vars.iter().filter(|(_, var)| var.exported) {
+            writeln!(shell.stdout(), "export {name}={}", quote(&var.value))?;
+        }
+        return Ok(0);
+    }
+
+    let mut status = 0;
+    for arg in names {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+        if !is_valid_name(name) {
+            shell.print_error(format_args!("shell: export: `{arg}': not a valid identifier"));
+            status = 1;
+            continue;
+        }
+        if let Some(value) = value {
+            shell.vars.set(name, value.to_string());
+        }
+        if unexport {
+            shell.vars.unexport(name);
+        } else {
+            shell.vars.export(name);
+        }
+    }
+    Ok(status)
+}
+
+/// `unset [-v|-f] NAME...` unsets the variables, or the functions with `-f`
+pub fn unset(shell: &mut Shell, args: &[String]) -> Result<i32, ShellError> {
+    let (functions, names) = match args.get(1).map(String::as_str) {
+        Some("-v") => (false, &args[2..]),
+        Some("-f") => (true, &args[2..]),
+        _ => (false, &args[1..]),
+    };
+    let mut status = 0;
+
+    for name in names {
+        if functions {
+            shell.functions.remove(name);
+            continue;
+        }
+        if !is_valid_name(name) {
+            shell.print_error(format_args!("shell: unset: `{name}': not a valid identifier"));
+            status = 1;
+            continue;
+        }
+        shell.vars.unset(name);
+    }
+    Ok(status)
+}
+
+/// `local [NAME[=VALUE]]...` makes the variables local to the function being called
+pub fn local(shell: &mut Shell, args: &[String]) -> Result<i32, ShellError> {
+    if shell.func_depth == 0 {
+        return Err(ShellError::Builtin("can only be used in a function".to_string()));
+    }
+    let mut status = 0;
+
+    for arg in &args[1..] {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+        if !is_valid_name(name) {
+            shell.print_error(format_args!("shell: local: `{arg}': not a valid identifier"));
+            status = 1;
+            continue;
+        }
+        shell.vars.make_local(name);
+        if let Some(value) = value {
+            shell.vars.set(name, value.to_string());
+        }
+    }
+    Ok(status)
+}
+
+/// `set [-eux] [+eux] [-o OPTION] [+o OPTION] [--] [ARG]...` sets or unsets the options,
+/// and replaces the positional parameters if any `ARG` or `--` is given.
+///
+/// Without arguments, it shows the variables.
+pub fn set(shell: &mut Shell, args: &[String]) -> Result<i32, ShellError> {
+    if args.len() == 1 {
+        for (name, var) in shell.vars.iter() {
+            writeln!(shell.stdout(), "{name}={}", quote(&var.value))?;
+        }
+        return Ok(0);
+    }
+
+    let mut args = args[1..].iter();
+    let mut positional = None;
+    while let Some(arg) = args.next() {
+        let (on, flags) = match arg.split_at_checked(1) {
+            Some(("-", flags)) => (true, flags),
+            Some(("+", flags)) => (false, flags),
+            _ => {
+                positional = Some(std::iter::once(arg).chain(args).cloned().collect());
+                break;
+            },
+        };
+        match flags {
+            "-" if on => {
+                positional = Some(args.cloned().collect());
+                break;
+            },
+            "o" => match args.next() {
+                Some(name) => match shell.opts.get_mut(name) {
+                    Some(opt) => *opt = on,
+                    None => return Err(ShellError::Usage(format!("{name}: invalid option name"))),
+                },
+                None => show_options(shell, on)?,
+            },
+            _ => for flag in flags.chars() {
+                match SetOptions::long_name(flag).and_then(|name| shell.opts.get_mut(name)) {
+                    Some(opt) => *opt = on,
+                    None => return Err(ShellError::Usage(format!("{}{flag}: invalid option", if on { '-' } else { '+' }))),
+                }
+            },
+        }
+    }
+
+    if let Some(positional) = positional {
+        shell.positional = positional;
+    }
+    Ok(0)
+}
+
+/// Shows the options for `set -o`, or as the commands to set them for `set +o`
+fn show_options(shell: &mut Shell, readable: bool) -> Result<(), ShellError> {
+    for name in SetOptions::NAMES {
+        let on = *shell.opts.get_mut(name).unwrap();
+        match readable {
+            true => writeln!(shell.stdout(), "{name:<16}{}", if on { "on" } else { "off" })?,
+            false => writeln!(shell.stdout(), "set {}o {name}", if on { '-' } else { '+' })?,
+        }
     }
-    println!("{}", env::current_dir()?.display());
     Ok(())
 }
+
+/// `shift [N]` removes the first N positional parameters, which defaults to 1
+pub fn shift(shell: &mut Shell, args: &[String]) -> Result<i32, ShellError> {
+    let n = match args.get(1) {
+        Some(n) => n.parse().map_err(|_| ShellError::Usage(format!("{n}: numeric argument required")))?,
+        None => 1,
+    };
+    if n > shell.positional.len() {
+        return Err(ShellError::Builtin("shift count out of range".to_string()));
+    }
+    shell.positional.drain(..n);
+    Ok(0)
+}
+
+/// `break [N]` exits from the N-th enclosing loop, which defaults to 1
+pub fn break_loop(shell: &mut Shell, args: &[String]) -> Result<i32, ShellError> {
+    shell.flow = Some(Flow::Break(loop_count(shell, args)?));
+    Ok(0)
+}
+
+/// `continue [N]` resumes the next iteration of the N-th enclosing loop, which defaults to 1
+pub fn continue_loop(shell: &mut Shell, args: &[String]) -> Result<i32, ShellError> {
+    shell.flow = Some(Flow::Continue(loop_count(shell, args)?));
+    Ok(0)
+}
+
+/// Returns the number of the loops to break or continue, which is at most the number of the enclosing ones
+fn loop_count(shell: &Shell, args: &[String]) -> Result<usize, ShellError> {
+    if shell.loop_depth == 0 {
+        return Err(ShellError::Builtin("only meaningful in a `for`, `while`, or `until` loop".to_string()));
+    }
+    let n: usize = match args.get(1) {
+        Some(n) => n.parse().map_err(|_| ShellError::Usage(format!("{n}: numeric argument required")))?,
+        None => 1,
+    };
+    if n == 0 {
+        return Err(ShellError::Builtin("0: loop count out of range".to_string()));
+    }
+    Ok(n.min(shell.loop_depth))
+}
+
+/// `shopt [-s|-u] [OPTNAME]...` sets or unsets the options, or shows them if neither `-s` nor `-u` is given
+pub fn shopt(shell: &mut Shell, args: &[String]) -> Result<i32, ShellError> {
+    let mut args = &args[1..];
+    let set_to = match args.first().map(String::as_str) {
+        Some("-s") => Some(true),
+        Some("-u") => Some(false),
+        Some(opt) if opt.starts_with('-') => return Err(ShellError::Usage(format!("{opt}: invalid option"))),
+        _ => None,
+    };
+    if set_to.is_some() {
+        args = &args[1..];
+    }
+    let names: Vec<&str> = match args.is_empty() {
+        true => Shopts::NAMES.to_vec(),
+        false => args.iter().map(String::as_str).collect(),
+    };
+
+    let mut status = 0;
+    for name in names {
+        let Some(opt) = shell.shopts.get_mut(name) else {
+            shell.print_error(format_args!("shell: shopt: {name}: invalid option name"));
+            status = 1;
+            continue;
+        };
+        match set_to {
+            Some(value) => *opt = value,
+            // like Bash, listing the given options tells whether they are all set
+            None if args.is_empty() || *opt => {
+                let on = *opt;
+                writeln!(shell.stdout(), "{name:<16}{}", if on { "on" } else { "off" })?;
+            },
+            None => {
+                writeln!(shell.stdout(), "{name:<16}off")?;
+                status = 1;
+            },
+        }
+    }
+    Ok(status)
+}
+
+/// `test EXPR` or `[ EXPR ]` returns 0 if the expression is true, or 1 otherwise
+pub fn test(shell: &Shell, args: &[String]) -> Result<i32, ShellError> {
+    let mut operands = &args[1..];
+    if args[0] == "[" {
+        match operands.split_last() {
+            Some((last, rest)) if last == "]" => operands = rest,
+            _ => return Err(ShellError::Usage("missing `]`".to_string())),
+        }
+    }
+    Ok(!cond::test(shell, operands)? as i32)
+}
+
+/// `complete [-W WORDS | -F FUNC | -r] [NAME]...` sets how the arguments of the commands are completed,
+/// or removes the settings with `-r`
+///
+/// Without `NAME`, it shows the settings, which can be reused as input.
+pub fn complete(shell: &mut Shell, args: &[String]) -> Result<i32, ShellError> {
+    let mut spec = None;
+    let mut remove = false;
+    let mut i = 1;
+    while let Some(arg) = args.get(i) {
+        match arg.as_str() {
+            "-W" | "-F" => {
+                let Some(value) = args.get(i + 1) else {
+                    return Err(ShellError::Usage(format!("{arg}: option requires an argument")));
+                };
+                spec = Some(match arg.as_str() {
+                    "-W" => CompletionSpec::Words(value.clone()),
+                    _ => CompletionSpec::Function(value.clone()),
+                });
+                i += 1;
+            },
+            "-r" => remove = true,
+            "-p" => {},
+            "--" => {
+                i += 1;
+                break;
+            },
+            _ if arg.starts_with('-') => return Err(ShellError::Usage(format!("{arg}: invalid option"))),
+            _ => break,
+        }
+        i += 1;
+    }
+    let names = &args[i..];
+
+    if remove {
+        if names.is_empty() {
+            shell.completions.clear();
+        }
+        let mut status = 0;
+        for name in names {
+            if shell.completions.remove(name).is_none() {
+                shell.print_error(format_args!("shell: complete: {name}: no completion specification"));
+                status = 1;
+            }
+        }
+        return Ok(status);
+    }
+    let Some(spec) = spec else {
+        return show_completions(shell, names);
+    };
+    if names.is_empty() {
+        return Err(ShellError::Usage("a command name is required".to_string()));
+    }
+    for name in names {
+        shell.completions.insert(name.clone(), spec.clone());
+    }
+    Ok(0)
+}
+
+/// Shows the settings of `complete` for the commands, or all of them in order if none is given
+fn show_completions(shell: &Shell, names: &[String]) -> Result<i32, ShellError> {
+    let names: Vec<&String> = match names.is_empty() {
+        true => {
+            let mut names: Vec<&String> = shell.completions.keys().collect();
+            names.sort();
+            names
+        },
+        false => names.iter().collect(),
+    };
+    let mut status = 0;
+    for name in names {
+        match shell.completions.get(name) {
+            Some(CompletionSpec::Words(words)) => writeln!(shell.stdout(), "complete -W {} {}", quote(words), quote(name))?,
+            Some(CompletionSpec::Function(func)) => writeln!(shell.stdout(), "complete -F {} {}", quote(func), quote(name))?,
+            None => {
+                shell.print_error(format_args!("shell: complete: {name}: no completion specification"));
+                status = 1;
+            },
+        }
+    }
+    Ok(status)
+}
+
+/// `source FILE [ARG]...` or `. FILE [ARG]...` executes the commands in the file in the current shell,
+/// where the arguments replace the positional parameters while it runs
+///
+/// A file name without `/` is searched for in `$PATH`, and then in the current directory.
+pub fn source(shell: &mut Shell, args: &[String]) -> Result<i32, ShellError> {
+    let Some(name) = args.get(1) else {
+        return Err(ShellError::Usage("filename argument required".to_string()));
+    };
+    let path = match name.contains('/') {
+        true => None,
+        false => shell.vars.get("PATH").unwrap_or_default()
+            .split(':')
+            .filter(|dir| !dir.is_empty())
+            .map(|dir| Path::new(dir).join(name))
+            .find(|path| shell.path(path).is_file()),
+    };
+    let path = path.unwrap_or_else(|| PathBuf::from(name));
+    repl::source(shell, &path, (args.len() > 2).then(|| &args[2..]))
+}
+
+/// `alias [-p] [NAME[=VALUE]]...` defines the aliases, or shows the ones given by name
+///
+/// Without `NAME`, it shows all of them, which can be reused as input.
+pub fn alias(shell: &mut Shell, args: &[String]) -> Result<i32, ShellError> {
+    let args = match args.get(1).map(String::as_str) {
+        Some("-p") => &args[2..],
+        _ => &args[1..],
+    };
+    let show = |shell: &Shell, name: &str, value: &str| writeln!(shell.stdout(), "alias {name}={}", quote(value));
+    if args.is_empty() {
+        let mut aliases: Vec<_> = shell.aliases.iter().collect();
+        aliases.sort();
+        for (name, value) in aliases {
+            show(shell, name, value)?;
+        }
+        return Ok(0);
+    }
+
+    let mut status = 0;
+    for arg in args {
+        match arg.split_once('=') {
+            Some((name, _)) if !alias::is_valid_name(name) => {
+                shell.print_error(format_args!("shell: alias: `{name}': invalid alias name"));
+                status = 1;
+            },
+            Some((name, value)) => {
+                shell.aliases.insert(name.to_string(), value.to_string());
+            },
+            None => match shell.aliases.get(arg) {
+                Some(value) => show(shell, arg, value)?,
+                None => {
+                    shell.print_error(format_args!("shell: alias: {arg}: not found"));
+                    status = 1;
+                },
+            },
+        }
+    }
+    Ok(status)
+}
+
+/// `unalias -a` removes all the aliases, and `unalias NAME...` removes the ones given
+pub fn unalias(shell: &mut Shell, args: &[String]) -> Result<i32, ShellError> {
+    match args.get(1).map(String::as_str) {
+        None => return Err(ShellError::Usage("usage: unalias [-a] NAME...".to_string())),
+        Some("-a") => {
+            shell.aliases.clear();
+            return Ok(0);
+        },
+        _ => {},
+    }
+    let mut status = 0;
+    for name in &args[1..] {
+        if shell.aliases.remove(name).is_none() {
+            shell.print_error(format_args!("shell: unalias: {name}: not found"));
+            status = 1;
+        }
+    }
+    Ok(status)
+}
@@ -1,19 +1,50 @@
-use nix::{unistd::execvp, errno::Errno};
+use nix::errno::Errno;
+use nix::unistd::{access, execve, AccessFlags};
 use std::ffi::CString;
+use std::path::PathBuf;
 
-use crate::types::error::GenericError;
+use crate::types::error::ShellError;
 
-pub fn exec(args: &Vec<String>) -> Result<(), GenericError> {
-    let filename = CString::new(args[0].as_bytes()).unwrap();
-    let cargs: Vec<CString> = args.iter()
-        .map(|arg| CString::new(arg.as_bytes()).unwrap())
-        .collect();
+/// Returns the path to the executable,
+/// which is searched in the directories listed in `path_var` if `name` doesn't contain `/`
+pub fn find_executable(name: &str, path_var: &str) -> Option<PathBuf> {
+    if name.contains('/') {
+        return Some(PathBuf::from(name));
+    }
+    path_var.split(':')
+        // an empty entry means the current directory
+        .map(|dir| PathBuf::from(if dir.is_empty() { "." } else { dir }).join(name))
+        .find(|path| path.is_file() && access(path, AccessFlags::X_OK).is_ok())
+}
 
-    match execvp(&filename, &cargs) {
-        Err(err) => match err {
-            Errno::ENOENT => return Err("command not found!".to_string().into()),
-            _ => return Err(err.desc().to_string().into()),
-        },
+/// Returns the reason only if the command fails to be executed
+pub fn exec(args: &[String], path_var: &str, env: &[CString]) -> ShellError {
+    let Some(path) = find_executable(&args[0], path_var) else {
+        return ShellError::CommandNotFound;
+    };
+    // A NUL byte can't be passed to the program, which is an invalid argument.
+    let Ok(filename) = CString::new(path.as_os_str().as_encoded_bytes()) else {
+        return ShellError::Exec(Errno::EINVAL);
+    };
+    let Ok(cargs) = args.iter().map(|arg| CString::new(arg.as_bytes())).collect::<Result<Vec<_>, _>>() else {
+        return ShellError::Exec(Errno::EINVAL);
+    };
+
+    match execve(&filename, &cargs, env) {
+        Err(errno) => ShellError::Exec(errno),
         Ok(_) => unreachable!(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exec_rejects_nul_in_arguments() {
+        let args = ["/bin/echo".to_string(), "a\0b".to_string()];
+        let err = exec(&args, "", &[]);
+        assert!(matches!(err, ShellError::Exec(Errno::EINVAL)));
+        assert_eq!(err.status(), 126);
+    }
+}
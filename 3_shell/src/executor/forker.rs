@@ -1,30 +1,77 @@
-use nix::{errno::Errno, sys::wait::waitpid, unistd::{ForkResult, Pid}};
+use nix::{errno::Errno, unistd::{setpgid, ForkResult, Pid}};
+use std::os::fd::RawFd;
+
+use crate::executor::jobs::set_foreground;
+use crate::signals;
 
 pub struct Forker {
     child_pids: Vec<Pid>,
+    /// `Some(foreground)` if the children are put into their own process group
+    job_control: Option<bool>,
+    pgid: Option<Pid>,
+    /// the file descriptor the children close right after they are forked
+    unused_fd: Option<RawFd>,
 }
 
 impl Forker {
-    pub fn new() -> Self {
-        Self { child_pids: vec![] }
+    /// If `job_control` is `Some(foreground)`, all children are put into the process group led by the first one,
+    /// and the group is handed the terminal if `foreground`.
+    pub fn new(job_control: Option<bool>) -> Self {
+        Self { child_pids: vec![], job_control, pgid: None, unused_fd: None }
+    }
+
+    /// Sets the file descriptor of the parent that the children shouldn't keep open,
+    /// e.g., the read end of the pipe from the command being forked
+    pub fn set_unused_fd(&mut self, fd: Option<RawFd>) {
+        self.unused_fd = fd;
     }
 
     /// Returns if it is a child process
     pub fn fork(&mut self) -> Result<bool, Errno> {
-        match unsafe {nix::unistd::fork()} {
+        let res = unsafe {nix::unistd::fork()};
+
+        // Both the parent and the child set the process group to avoid the race condition.
+        if let (Some(foreground), Ok(fork_res)) = (self.job_control, &res) {
+            let pid = match fork_res {
+                ForkResult::Parent { child } => *child,
+                ForkResult::Child => nix::unistd::getpid(),
+            };
+            let pgid = *self.pgid.get_or_insert(pid);
+            let _ = setpgid(pid, pgid);
+            if foreground {
+                set_foreground(pgid);
+            }
+        }
+
+        match res {
             Ok(ForkResult::Parent { child, .. }) => {
                 self.child_pids.push(child);
-                return Ok(false);
+                Ok(false)
+            },
+            Ok(ForkResult::Child) => {
+                signals::reset();
+                // Rust ignores SIGPIPE, but a child writing to a closed pipe is killed by it like in other shells.
+                unsafe { libc::signal(libc::SIGPIPE, libc::SIG_DFL); }
+                // It's replaced with /dev/null rather than closed, since its owner still closes it in the child.
+                if let Some(fd) = self.unused_fd {
+                    unsafe {
+                        let null = libc::open(c"/dev/null".as_ptr(), libc::O_RDONLY);
+                        libc::dup2(null, fd);
+                        libc::close(null);
+                    }
+                }
+                Ok(true)
             },
-            Ok(ForkResult::Child) => return Ok(true),
-            Err(err) => return Err(err),
+            Err(err) => Err(err),
         }
     }
 
-    pub fn wait_all(&mut self) {
-        while !self.child_pids.is_empty() {
-            let pid = self.child_pids.pop();
-            waitpid(pid, None).unwrap();
-        }
+    pub fn pgid(&self) -> Option<Pid> {
+        self.pgid
+    }
+
+    /// Returns the pids of the children in the order they are forked
+    pub fn into_pids(self) -> Vec<Pid> {
+        self.child_pids
     }
 }
@@ -0,0 +1,287 @@
+use nix::{
+    errno::Errno,
+    sys::{
+        signal::{killpg, kill, SigSet, Signal},
+        wait::{waitpid, WaitPidFlag, WaitStatus},
+    },
+    unistd::{tcsetpgrp, Pid},
+};
+use std::io::stdin;
+
+use crate::types::error::ShellError;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum JobState {
+    Running,
+    Stopped,
+    Done(i32),
+}
+
+struct Process {
+    pid: Pid,
+    state: JobState,
+}
+
+pub struct Job {
+    pub id: usize,
+    /// `None` if job control is disabled when the job is created
+    pub pgid: Option<Pid>,
+    pub text: String,
+    procs: Vec<Process>,
+    /// whether the user has been told about the current state
+    notified: bool,
+}
+
+impl Job {
+    /// `pids` should be in the order they are forked, so that the last one is the last command of the pipeline.
+    pub fn new(pgid: Option<Pid>, pids: Vec<Pid>, text: String) -> Self {
+        Self {
+            id: 0,
+            pgid,
+            text,
+            procs: pids.into_iter().map(|pid| Process { pid, state: JobState::Running }).collect(),
+            notified: false,
+        }
+    }
+
+    pub fn last_pid(&self) -> Option<Pid> {
+        self.procs.last().map(|proc| proc.pid)
+    }
+
+    /// The job is done only if all its processes are done, and its status is the one of the last process.
+    pub fn state(&self) -> JobState {
+        if self.procs.iter().any(|proc| proc.state == JobState::Running) {
+            return JobState::Running;
+        }
+        if self.procs.iter().any(|proc| proc.state == JobState::Stopped) {
+            return JobState::Stopped;
+        }
+        match self.procs.last() {
+            Some(proc) => proc.state,
+            None => JobState::Done(0),
+        }
+    }
+
+    fn contains(&self, pid: Pid) -> bool {
+        self.procs.iter().any(|proc| proc.pid == pid)
+    }
+
+    fn update(&mut self, wait_status: WaitStatus) {
+        let (pid, state) = match wait_status {
+            WaitStatus::Exited(pid, code) => (pid, JobState::Done(code)),
+            WaitStatus::Signaled(pid, sig, _) => (pid, JobState::Done(128 + sig as i32)),
+            WaitStatus::Stopped(pid, _) => (pid, JobState::Stopped),
+            WaitStatus::Continued(pid) => (pid, JobState::Running),
+            _ => return,
+        };
+        if let Some(proc) = self.procs.iter_mut().find(|proc| proc.pid == pid)
+            && proc.state != state {
+            proc.state = state;
+            self.notified = false;
+        }
+    }
+
+    /// Blocks until the job is either done or stopped and returns its state,
+    /// which is still `JobState::Running` if the waiting is interrupted by a signal
+    pub fn wait(&mut self) -> JobState {
+        while self.state() == JobState::Running {
+            let res = match self.pgid {
+                // waiting for the whole group lets us notice any of its processes gets stopped
+                Some(pgid) => waitpid(Pid::from_raw(-pgid.as_raw()), Some(WaitPidFlag::WUNTRACED)),
+                None => {
+                    let pid = self.procs.iter()
+                        .find(|proc| proc.state == JobState::Running)
+                        .map(|proc| proc.pid);
+                    waitpid(pid, None)
+                },
+            };
+            match res {
+                Ok(wait_status) => self.update(wait_status),
+                Err(Errno::EINTR) => break,
+                // the processes have been reaped by someone else
+                Err(_) => self.procs.iter_mut().for_each(|proc| if proc.state == JobState::Running {
+                    proc.state = JobState::Done(1);
+                }),
+            }
+        }
+        self.state()
+    }
+
+    /// Updates the states of its processes without blocking
+    fn poll(&mut self) {
+        let flags = WaitPidFlag::WNOHANG | WaitPidFlag::WUNTRACED | WaitPidFlag::WCONTINUED;
+
+        for i in 0..self.procs.len() {
+            if let JobState::Done(_) = self.procs[i].state {
+                continue;
+            }
+            match waitpid(self.procs[i].pid, Some(flags)) {
+                Ok(WaitStatus::StillAlive) => {},
+                Ok(wait_status) => self.update(wait_status),
+                Err(_) => self.procs[i].state = JobState::Done(1),
+            }
+        }
+    }
+
+    /// Resumes its stopped processes
+    pub fn resume(&mut self) -> Result<(), ShellError> {
+        let res = match self.pgid {
+            Some(pgid) => killpg(pgid, Signal::SIGCONT),
+            None => self.procs.iter().try_for_each(|proc| kill(proc.pid, Signal::SIGCONT)),
+        };
+        if res.is_err() {
+            return Err(ShellError::Job("kill: failed to send SIGCONT".to_string()));
+        }
+        for proc in self.procs.iter_mut() {
+            if proc.state == JobState::Stopped {
+                proc.state = JobState::Running;
+            }
+        }
+        self.notified = false;
+        Ok(())
+    }
+}
+
+/// Job IDs are assigned incrementally from 1, and they are reused once the table is empty.
+#[derive(Default)]
+pub struct JobTable {
+    jobs: Vec<Job>,
+    /// job IDs ordered from the least to the most recently started, stopped or resumed,
+    /// so that the last one is the current job and the second last one is the previous job.
+    recency: Vec<usize>,
+}
+
+impl JobTable {
+    pub fn new() -> Self {
+        Self { jobs: vec![], recency: vec![] }
+    }
+
+    /// Returns the assigned job ID
+    pub fn add(&mut self, mut job: Job) -> usize {
+        job.id = self.jobs.last().map_or(1, |last| last.id + 1);
+        self.recency.push(job.id);
+        self.jobs.push(job);
+        self.jobs.last().unwrap().id
+    }
+
+    pub fn remove(&mut self, id: usize) -> Option<Job> {
+        self.recency.retain(|&recent| recent != id);
+        let idx = self.jobs.iter().position(|job| job.id == id)?;
+        Some(self.jobs.remove(idx))
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Job> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Job> {
+        self.jobs.iter_mut().find(|job| job.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Job> {
+        self.jobs.iter()
+    }
+
+    /// Makes the job the current one
+    pub fn touch(&mut self, id: usize) {
+        self.recency.retain(|&recent| recent != id);
+        self.recency.push(id);
+    }
+
+    /// Returns `'+'` for the current job, `'-'` for the previous job, or `' '` otherwise
+    pub fn mark(&self, id: usize) -> char {
+        let mut recent = self.recency.iter().rev();
+        if recent.next() == Some(&id) {
+            return '+';
+        }
+        if recent.next() == Some(&id) {
+            return '-';
+        }
+        ' '
+    }
+
+    /// Resolves a job spec, i.e., `%N`, `%+`, `%%`, `%-`, `%PREFIX` or `N`, into a job ID
+    pub fn resolve(&self, spec: &str) -> Result<usize, ShellError> {
+        let spec_body = spec.strip_prefix('%').unwrap_or(spec);
+        let id = match spec_body {
+            "" | "+" | "%" => self.recency.last().copied(),
+            "-" => self.recency.iter().rev().nth(1).copied(),
+            _ => match spec_body.parse::<usize>() {
+                Ok(n) => self.jobs.iter().find(|job| job.id == n).map(|job| job.id),
+                Err(_) => {
+                    let mut candidates = self.jobs.iter().filter(|job| job.text.starts_with(spec_body));
+                    match (candidates.next(), candidates.next()) {
+                        (Some(job), None) => Some(job.id),
+                        (Some(_), Some(_)) => return Err(ShellError::Job(format!("{spec}: ambiguous job spec"))),
+                        _ => None,
+                    }
+                },
+            },
+        };
+        id.ok_or_else(|| ShellError::Job(format!("{spec}: no such job")))
+    }
+
+    /// Returns the ID of the job containing the process
+    pub fn find_by_pid(&self, pid: Pid) -> Option<usize> {
+        self.jobs.iter().find(|job| job.contains(pid)).map(|job| job.id)
+    }
+
+    /// Updates the states of all jobs without blocking
+    pub fn update(&mut self) {
+        for job in self.jobs.iter_mut() {
+            job.poll();
+        }
+    }
+
+    /// Marks all jobs as notified and removes the done ones
+    pub fn clean(&mut self) {
+        for job in self.jobs.iter_mut() {
+            job.notified = true;
+        }
+        let done_ids: Vec<usize> = self.jobs.iter()
+            .filter(|job| matches!(job.state(), JobState::Done(_)))
+            .map(|job| job.id)
+            .collect();
+        for id in done_ids {
+            self.remove(id);
+        }
+    }
+
+    /// Updates the states of all jobs and removes the done ones.
+    /// If `verbose`, returns the reports of the jobs whose states have changed, e.g., `[1]+  Done    sleep 1`.
+    pub fn notify(&mut self, verbose: bool) -> Vec<String> {
+        self.update();
+        let reports = match verbose {
+            true => self.jobs.iter()
+                .filter(|job| !job.notified && job.state() != JobState::Running)
+                .map(|job| self.format(job))
+                .collect(),
+            false => vec![],
+        };
+        self.clean();
+        reports
+    }
+
+    /// e.g., `[1]+  Running                 sleep 10 &`
+    pub fn format(&self, job: &Job) -> String {
+        let (state, suffix) = match job.state() {
+            JobState::Running => ("Running".to_string(), " &"),
+            JobState::Stopped => ("Stopped".to_string(), ""),
+            JobState::Done(0) => ("Done".to_string(), ""),
+            JobState::Done(status) => (format!("Exit {status}"), ""),
+        };
+        format!("[{}]{}  {:<24}{}{}", job.id, self.mark(job.id), state, job.text, suffix)
+    }
+}
+
+/// Hands the terminal over to the process group.
+///
+/// `SIGTTOU` is blocked during the handover,
+/// since it is sent to a process which isn't in the foreground process group.
+pub fn set_foreground(pgid: Pid) {
+    let mut sigset = SigSet::empty();
+    sigset.add(Signal::SIGTTOU);
+    let _ = sigset.thread_block();
+    let _ = tcsetpgrp(stdin(), pgid);
+    let _ = sigset.thread_unblock();
+}
@@ -1,67 +1,215 @@
-use nix::unistd::pipe;
-use std::{fs::File, os::fd::AsFd};
+use nix::{sys::signal::Signal, unistd::pipe};
+use std::{env, fs::File, io::Read, os::fd::{AsRawFd, RawFd}};
 
-use crate::types::{command::Command, error::GenericError};
-use crate::executor::redirect::{merge, spread};
+use crate::expand::{expand_pattern, expand_word, expand_words};
+use crate::alias;
+use crate::arith;
+use crate::cond;
+use crate::glob;
+use crate::shell::{Flow, Shell, MIN_SHELL_FD};
+use crate::parser::quote;
+use crate::types::command::{
+    AndOrList, AndOrOp, CaseItem, CaseTerminator, Command, CommandList, CompoundCommand, CondExpr, Pipeline,
+    SimpleCommand,
+};
+use crate::types::{error::ShellError, word::Word};
+use crate::variables::Variable;
 
 mod builtins;
 mod exec;
 mod forker;
-#[macro_use]
+pub mod jobs;
 mod redirect;
 
-/// Returns whether to exit
-pub fn exec_cmds(cmds: Vec<Command>) -> Result<bool, GenericError> {
-    let mut should_exit = false;
+/// How deep functions can be called recursively
+const MAX_FUNC_DEPTH: usize = 1000;
+
+/// Executes the and-or lists one by one, and updates the exit status of the last executed pipeline
+///
+/// It stops early on `break` or `continue`, or if interrupted.
+pub fn exec_list(shell: &mut Shell, list: &CommandList) {
+    for and_or_list in &list.items {
+        if shell.flow.is_some() {
+            break;
+        }
+        if and_or_list.background {
+            if let Err(err) = exec_in_background(shell, and_or_list) {
+                shell.print_error(format_args!("shell: {err}"));
+                shell.last_status = err.status();
+            }
+        } else {
+            exec_and_or_list(shell, and_or_list);
+        }
+    }
+}
+
+/// A pipeline is skipped if it follows `&&` and the previous status is non-zero,
+/// or if it follows `||` and the previous status is zero.
+fn exec_and_or_list(shell: &mut Shell, and_or_list: &AndOrList) {
+    let pipelines = std::iter::once((None, &and_or_list.first))
+        .chain(and_or_list.rest.iter().map(|(op, pipeline)| (Some(op), pipeline)));
+
+    let last_idx = and_or_list.rest.len();
+    // whether the last executed pipeline is the last one of the list
+    let mut is_last_executed = false;
+
+    for (i, (op, pipeline)) in pipelines.enumerate() {
+        let should_run = match op {
+            None => true,
+            Some(AndOrOp::And) => shell.last_status == 0,
+            Some(AndOrOp::Or) => shell.last_status != 0,
+        };
+        if !should_run {
+            continue;
+        }
+        if shell.flow.is_some() {
+            break;
+        }
+        shell.last_status = match exec_cmds(shell, pipeline) {
+            Ok(status) => status,
+            Err(err) => {
+                shell.print_error(format_args!("shell: {err}"));
+                err.status()
+            },
+        };
+        is_last_executed = i == last_idx && !pipeline.negated;
+    }
+
+    // The shell exits with the status given to `exit` even if it is negated, e.g., `! exit 3`.
+    if let Some(Flow::Exit(status)) = shell.flow {
+        shell.last_status = status;
+        return;
+    }
+
+    // `set -e` ignores the failures of the pipelines followed by `&&` or `||`, negated by `!`,
+    // or tested by `if`, `while` or `until`.
+    if shell.opts.errexit && shell.last_status != 0 && is_last_executed && shell.cond_depth == 0 {
+        shell.flow = Some(Flow::Exit(shell.last_status));
+    }
+}
+
+/// Executes the and-or list in a subshell without waiting for it, and adds it to the job table
+fn exec_in_background(shell: &mut Shell, and_or_list: &AndOrList) -> Result<(), ShellError> {
+    let mut forker = forker::Forker::new(shell.job_control.then_some(false));
+
+    match forker.fork() {
+        Ok(true) => {
+            // Without job control, the job could race with the shell for the input.
+            let stdin = match shell.job_control {
+                true => None,
+                false => File::open("/dev/null").ok(),
+            };
+            enter_child(shell, stdin.map(|file| (0, Some(file))).into_iter().collect());
+            exec_and_or_list(shell, and_or_list);
+            // child process exits here
+            unsafe { libc::_exit(shell.last_status); }
+        },
+        Ok(false) => {},
+        Err(errno) => return Err(ShellError::internal("fork: failed to fork the background job", errno)),
+    }
+
+    let job = jobs::Job::new(forker.pgid(), forker.into_pids(), and_or_list.text.clone());
+    let pid = job.last_pid().map_or(0, |pid| pid.as_raw());
+    shell.last_bg_pid = job.last_pid();
+    let id = shell.jobs.add(job);
+    if shell.job_control {
+        shell.print_error(format_args!("[{id}] {pid}"));
+    }
+    shell.last_status = 0;
+    Ok(())
+}
+
+/// Executes the command list in a subshell whose stdout is captured, and returns the output and the exit status
+pub fn exec_captured(shell: &mut Shell, list: &CommandList) -> Result<(String, i32), ShellError> {
+    let (fd_read, fd_write) = pipe()
+        .map_err(|errno| ShellError::internal("pipe: failed to create the pipe for command substitution", errno))?;
+    let mut forker = forker::Forker::new(None);
+
+    match forker.fork() {
+        Ok(true) => {
+            drop(fd_read);
+            enter_child(shell, vec![(1, Some(File::from(fd_write)))]);
+            exec_list(shell, list);
+            // child process exits here
+            unsafe { libc::_exit(shell.last_status); }
+        },
+        Ok(false) => {},
+        Err(errno) => return Err(ShellError::internal("fork: failed to fork the command substitution", errno)),
+    }
+    drop(fd_write);
+
+    let mut output = vec![];
+    let read_res = File::from(fd_read).read_to_end(&mut output);
+    let status = match jobs::Job::new(None, forker.into_pids(), String::new()).wait() {
+        jobs::JobState::Done(status) => status,
+        _ => 1,
+    };
+    read_res?;
+    Ok((String::from_utf8_lossy(&output).into_owned(), status))
+}
+
+/// Returns the exit status of the pipeline, i.e., the one of its last command, which is inverted by `!`
+///
+/// If the pipeline gets stopped, it is added to the job table.
+pub fn exec_cmds(shell: &mut Shell, pipeline: &Pipeline) -> Result<i32, ShellError> {
+    let status = exec_pipeline(shell, &pipeline.cmds, &pipeline.text)?;
+    Ok(if pipeline.negated { (status == 0) as i32 } else { status })
+}
+
+/// Executes the commands connected by pipes, where `text` is shown if they become a job
+fn exec_pipeline(shell: &mut Shell, cmds: &[Command], text: &str) -> Result<i32, ShellError> {
+    let mut status = None;
     let last_idx = cmds.len() - 1;
     let in_subshell = cmds.len() > 1;
     let mut file_in: Option<File> = None;
     let mut file_out: Option<File> = None;
     let mut file_in_next: Option<File> = None;
 
-    let mut forker = forker::Forker::new();
+    let mut forker = forker::Forker::new(shell.job_control.then_some(true));
     let mut err_res = None;
+    let aliases_in_use = shell.aliases_in_use.len();
 
-    for (i, cmd) in cmds.into_iter().enumerate() {
-        // merge input files and the pipe's read end
-        let mut files_in: Vec<File> = file_in
-            .take()
-            .into_iter()
-            .chain(
-                cmd.files_in
-                    .iter()
-                    .map(File::open)
-                    .collect::<Result<Vec<_>, _>>()?
-            )
-            .collect();
-
-        // If there're multiple input sources, merge them.
-        if files_in.len() > 1 {
-            match pipe() {
-                Ok(fds) => {
-                    match forker.fork() {
-                        Ok(is_child) => {
-                            if is_child {
-                                if let Err(err) = merge(&files_in, fds.1) {
-                                    eprintln!("shell: merge: failed with an error: {err}");
-                                }
-                                unsafe { libc::_exit(0); }
-                            }
-                            file_in = Some(File::from(fds.0));
-                        },
-                        Err(_) => {
-                            err_res = Some("fork: failed to create the merger".into());
-                            break;
-                        },
-                    }
+    for (i, cmd) in cmds.iter().enumerate() {
+        // The aliases are replaced before the command is expanded, and aren't replaced again while it runs.
+        shell.aliases_in_use.truncate(aliases_in_use);
+        let aliased = match cmd {
+            Command::Simple(cmd) => match alias::expand(shell, cmd) {
+                Ok(aliased) => aliased,
+                Err(err) => {
+                    err_res = Some(err);
+                    break;
                 },
-                Err(_) => {
-                    err_res = Some("pipe: failed to create the pipe connecting from the merger".into());
+            },
+            _ => None,
+        };
+        let cmd = match aliased {
+            Some((ref cmd, ref names)) => {
+                shell.aliases_in_use.extend(names.iter().cloned());
+                cmd
+            },
+            None => cmd,
+        };
+
+        let (expanded, redirects) = match cmd {
+            Command::Simple(cmd) => match expand_cmd(shell, cmd) {
+                Ok(expanded) => (Some(expanded), cmd.redirects.as_slice()),
+                Err(err) => {
+                    err_res = Some(err);
                     break;
                 },
-            }
-        } else {
-            file_in = files_in.pop().take();
+            },
+            Command::Compound { redirects, .. } => (None, redirects.as_slice()),
+            Command::FunctionDef { .. } => (None, [].as_slice()),
+        };
+        let runs_in_shell = !in_subshell && match (cmd, &expanded) {
+            (_, Some((_, args, _))) => args.first().is_some_and(|cmd_name| runs_in_shell(shell, cmd_name)),
+            (Command::Compound { body, .. }, _) => !matches!(body, CompoundCommand::Subshell(_)),
+            _ => true,
+        };
+        if runs_in_shell {
+            // The command runs in the shell itself,
+            // so the mergers and spreaders are not a job to which the terminal is handed over.
+            forker = forker::Forker::new(None);
         }
 
         if i < last_idx {
@@ -70,59 +218,40 @@ pub fn exec_cmds(cmds: Vec<Command>) -> Result<bool, GenericError> {
                     file_in_next = Some(File::from(fds.0));
                     file_out = Some(File::from(fds.1));
                 },
-                Err(_) => {
-                    err_res = Some("pipe: failed to create the pipe connecting two commands".into());
+                Err(errno) => {
+                    err_res = Some(ShellError::internal("pipe: failed to create the pipe connecting two commands", errno));
                     break;
                 },
             }
         }
+        // The command doesn't keep the read end of its own output open,
+        // which would keep it from getting SIGPIPE when the next command exits.
+        forker.set_unused_fd(file_in_next.as_ref().map(|file| file.as_raw_fd()));
 
-        // merge output files and the pipe's write end
-        let mut files_out: Vec<File> = file_out
-            .take()
-            .into_iter()
-            .chain(
-                cmd.files_out
-                    .iter()
-                    .map(File::create)
-                    .collect::<Result<Vec<_>, _>>()?
-            )
-            .collect();
-
-        // If there're multiple output destinations, spread to them.
-        if files_out.len() > 1 {
-            match pipe() {
-                Ok(fds) => {
-                    match forker.fork() {
-                        Ok(is_child) => {
-                            if is_child {
-                                // The pipe's write end should be closed in the child process
-                                // before spread() starts reading from the pipe's read end.
-                                drop(fds.1);
-                                if let Err(err) = spread(&mut files_out, fds.0) {
-                                    eprintln!("shell: spread: failed with an error: {err}");
-                                }
-                                unsafe { libc::_exit(0); }
-                            }
-                            file_out = Some(File::from(fds.1));
-                        },
-                        Err(_) => {
-                            err_res = Some("fork: failed to create the spreader".into());
-                            break;
-                        },
-                    }
-                },
-                Err(_) => {
-                    err_res = Some("pipe: failed to create the pipe connecting to the spreader".into());
-                    break;
-                }
-            }
-        } else {
-            file_out = files_out.pop().take();
-        }
+        // The pipes are merged with the input files or spread to the output files if any.
+        let fds = match redirect::resolve(shell, redirects, file_in.take(), file_out.take(), &mut forker) {
+            Ok(fds) => fds,
+            Err(err) => {
+                err_res = Some(err);
+                break;
+            },
+        };
 
-        match exec_cmd(cmd, file_in.take(), file_out.take(), in_subshell, &mut forker) {
-            Ok(exit_or_not) => should_exit |= exit_or_not,
+        let res = match (cmd, expanded) {
+            (Command::Compound { body, .. }, _) => exec_compound(shell, body, fds, in_subshell, &mut forker),
+            (Command::FunctionDef { name, body }, _) => {
+                // A function defined in a pipeline is logically defined in a subshell.
+                if !in_subshell {
+                    shell.functions.insert(name.clone(), body.clone());
+                }
+                Ok(Some(0))
+            },
+            (_, Some((assigns, args, subst_status))) => exec_cmd(shell, assigns, args, fds, in_subshell, &mut forker)
+                .map(|cmd_status| subst_status.or(cmd_status)),
+            (_, None) => unreachable!(),
+        };
+        match res {
+            Ok(cmd_status) => status = cmd_status,
             Err(err) => {
                 err_res = Some(err);
                 break;
@@ -132,89 +261,545 @@ pub fn exec_cmds(cmds: Vec<Command>) -> Result<bool, GenericError> {
         file_in = file_in_next.take();
     }
 
-    forker.wait_all();
+    shell.aliases_in_use.truncate(aliases_in_use);
+    // The pipes left by an error are closed, so that the commands forked so far don't block on them forever.
+    drop((file_in, file_out, file_in_next));
+    let last_forked_status = wait_job(shell, forker, text.to_string());
 
     if let Some(err) = err_res {
         return Err(err);
     }
-    Ok(should_exit)
+    // If the last command is not forked, its status is known without waiting.
+    Ok(status.or(last_forked_status).unwrap_or(0))
+}
+
+/// `NAME=value`s after expansion
+type Assignments = Vec<(String, String)>;
+
+/// Returns the expanded assignments and arguments of the command, which are traced for `set -x`,
+/// and the status of the last command substitution if the command has no name, e.g., `x=$(false)`
+fn expand_cmd(shell: &mut Shell, cmd: &SimpleCommand) -> Result<(Assignments, Vec<String>, Option<i32>), ShellError> {
+    let expanded = expand_words(shell, &cmd.args).and_then(|args| {
+        let assigns = cmd.assigns.iter()
+            .map(|(name, value)| Ok((name.clone(), expand_word(shell, value)?)))
+            .collect::<Result<Vec<_>, ShellError>>()?;
+        Ok((assigns, args))
+    });
+    let (assigns, args) = match expanded {
+        Ok(expanded) => expanded,
+        // A non-interactive shell exits on an expansion error, e.g., for an unbound variable.
+        Err(err) => {
+            if !shell.interactive {
+                shell.flow = Some(Flow::Exit(err.status()));
+            }
+            return Err(err);
+        },
+    };
+
+    if shell.opts.xtrace {
+        trace(shell, &assigns, &args);
+    }
+    let subst_status = (args.is_empty()
+        && cmd.args.iter().chain(cmd.assigns.iter().map(|(_, value)| value)).any(Word::has_cmd_subst))
+        .then_some(shell.last_status);
+    Ok((assigns, args, subst_status))
+}
+
+/// Prints the expanded command to stderr after `$PS4` for `set -x`
+fn trace(shell: &Shell, assigns: &[(String, String)], args: &[String]) {
+    let words: Vec<String> = assigns.iter()
+        .map(|(name, value)| format!("{name}={}", quote(value)))
+        .chain(args.iter().map(|arg| quote(arg)))
+        .collect();
+    if !words.is_empty() {
+        shell.print_error(format_args!("{}{}", shell.vars.get("PS4").unwrap_or("+ "), words.join(" ")));
+    }
 }
 
+/// Waits for the forked children as a foreground job, and returns the exit status of the last one if any
+fn wait_job(shell: &mut Shell, forker: forker::Forker, text: String) -> Option<i32> {
+    let mut job = jobs::Job::new(forker.pgid(), forker.into_pids(), text);
+    job.last_pid()?;
+
+    let state = job.wait();
+    if shell.job_control {
+        jobs::set_foreground(shell.pgid);
+    }
+
+    match state {
+        jobs::JobState::Done(status) => {
+            if shell.job_control && status == 128 + Signal::SIGINT as i32 {
+                // move to the next line of `^C`
+                shell.print_error("");
+                shell.flow = Some(Flow::Interrupt);
+            }
+            Some(status)
+        },
+        jobs::JobState::Stopped => {
+            // move to the next line of `^Z`
+            shell.print_error("");
+            shell.jobs.add(job);
+            shell.notify_jobs(true);
+            shell.flow = Some(Flow::Interrupt);
+            Some(128 + Signal::SIGTSTP as i32)
+        },
+        // Interrupted while the job still holds the shell's process group,
+        // so leave it running in the background.
+        jobs::JobState::Running => {
+            shell.jobs.add(job);
+            shell.flow = Some(Flow::Interrupt);
+            Some(128 + Signal::SIGINT as i32)
+        },
+    }
+}
+
+pub const BUILTIN_NAMES: [&str; 24] = [
+    "exit", "echo", "cd", "pwd", "jobs", "fg", "bg", "wait", "export", "unset", "set", "shopt", "shift",
+    "break", "continue", "return", "local", "test", "[", "complete", "source", ".", "alias", "unalias",
+];
+
 fn is_builtin(cmd_name: &str) -> bool {
-    static BUILTIN_NAMES: [&str; 4] = ["exit", "echo", "cd", "pwd"];
-    return BUILTIN_NAMES.contains(&cmd_name);
+    BUILTIN_NAMES.contains(&cmd_name)
 }
 
-/// Returns whether to exit or a generic error
+/// Functions and builtins run in the shell itself unless they are in a pipeline.
+fn runs_in_shell(shell: &Shell, cmd_name: &str) -> bool {
+    shell.functions.contains_key(cmd_name) || is_builtin(cmd_name) || shell.builtins.contains_key(cmd_name)
+}
+
+/// Calls the function with the arguments as its positional parameters, and returns the exit status
+///
+/// The variables made local by `local` are restored when it returns.
+fn call_function(shell: &mut Shell, body: &Command, args: &[String]) -> Result<i32, ShellError> {
+    if shell.func_depth >= MAX_FUNC_DEPTH {
+        let msg = format!("maximum function nesting level exceeded ({MAX_FUNC_DEPTH})");
+        return Err(ShellError::Internal { msg, source: None });
+    }
+    let positional = std::mem::replace(&mut shell.positional, args[1..].to_vec());
+    // `break` and `continue` don't reach the loops of the caller.
+    let loop_depth = std::mem::replace(&mut shell.loop_depth, 0);
+    shell.func_depth += 1;
+    shell.vars.push_scope();
+
+    let res = exec_pipeline(shell, std::slice::from_ref(body), &args[0]);
+
+    shell.vars.pop_scope();
+    shell.func_depth -= 1;
+    shell.loop_depth = loop_depth;
+    shell.positional = positional;
+
+    if let Some(Flow::Return(status)) = shell.flow {
+        shell.flow = None;
+        return Ok(status);
+    }
+    res
+}
+
+/// Calls the function named by the first argument, e.g., to generate the candidates of completion,
+/// or returns `None` if it is not defined
+pub fn call_function_by_name(shell: &mut Shell, args: &[String]) -> Option<Result<i32, ShellError>> {
+    let body = shell.functions.get(&args[0])?.clone();
+    Some(call_function(shell, &body, args))
+}
+
+/// Converts the result of a builtin into an exit status and an optional error
+fn builtin_status(res: Result<i32, ShellError>) -> (i32, Option<ShellError>) {
+    match res {
+        Ok(status) => (status, None),
+        Err(err) => (err.status(), Some(err)),
+    }
+}
+
+/// Returns the exit status if the command is not forked, `None` if it is, or a generic error
 ///
 /// Note: when `exit` is executed in a pipeline, the shell won't terminate because `exit` is logically executed
 /// in a in subshell.
+///
+/// The assignments are exported to the command only. If there's no command, they are set in the shell instead.
 fn exec_cmd(
-    cmd: Command,
-    fd_in: Option<File>,
-    fd_out: Option<File>,
+    shell: &mut Shell,
+    assigns: Vec<(String, String)>,
+    args: Vec<String>,
+    fds: Vec<(RawFd, Option<File>)>,
     in_subshell: bool,
     forker: &mut forker::Forker,
-) -> Result<bool, GenericError> {
-    let cmd_name = match cmd.args.first() {
+) -> Result<Option<i32>, ShellError> {
+    let cmd_name = match args.first() {
         Some(v) => v,
-        None => return Ok(false),
+        None => {
+            // Assignments in a pipeline are logically made in a subshell.
+            if !in_subshell {
+                for (name, value) in assigns {
+                    shell.vars.set(&name, value);
+                }
+            }
+            return Ok(Some(0));
+        },
+    };
+    let should_fork = !runs_in_shell(shell, cmd_name) || in_subshell;
+
+    let fds_pre = match should_fork {
+        true => {
+            match forker.fork() {
+                Ok(is_child) => if !is_child { return Ok(None) },
+                Err(errno) => return Err(ShellError::internal("fork: failed to fork", errno)),
+            }
+            enter_child(shell, fds);
+            vec![]
+        },
+        false => apply_fds(shell, fds)?,
+    };
+
+    // The variables are restored after the builtin if it's not forked.
+    let vars_pre: Vec<_> = assigns.into_iter()
+        .map(|(name, value)| {
+            let var_pre = shell.vars.insert(&name, Some(Variable { value, elements: None, exported: true }));
+            (name, var_pre)
+        })
+        .collect();
+
+    let func = shell.functions.get(cmd_name).cloned();
+    let (status, err) = match &func {
+        Some(func) => builtin_status(call_function(shell, func, &args)),
+        None => exec_builtin_or_program(shell, &args),
+    };
+
+    // A non-numeric status still ends a non-interactive shell with status 2 like in Bash.
+    let should_exit = cmd_name == "exit" && func.is_none()
+        && (err.is_none() || matches!(err, Some(ShellError::Usage(_))) && !shell.interactive);
+    if let Some(err) = err {
+        shell.print_error(format_args!("shell: {cmd_name}: {err}"));
+    }
+
+    if should_fork {
+        // child process exits here
+        unsafe { libc::_exit(status); }
+    }
+
+    for (name, var_pre) in vars_pre.into_iter().rev() {
+        shell.vars.insert(&name, var_pre);
+    }
+
+    restore_fds(shell, fds_pre);
+
+    if should_exit {
+        shell.flow = Some(Flow::Exit(status));
+    }
+    Ok(Some(status))
+}
+
+/// Returns the exit status if the compound command is not forked, `None` if it is, or a generic error
+///
+/// It is forked if it is a subshell or in a pipeline.
+fn exec_compound(
+    shell: &mut Shell,
+    body: &CompoundCommand,
+    fds: Vec<(RawFd, Option<File>)>,
+    in_subshell: bool,
+    forker: &mut forker::Forker,
+) -> Result<Option<i32>, ShellError> {
+    let should_fork = in_subshell || matches!(body, CompoundCommand::Subshell(_));
+
+    let fds_pre = match should_fork {
+        true => {
+            match forker.fork() {
+                Ok(is_child) => if !is_child { return Ok(None) },
+                Err(errno) => return Err(ShellError::internal("fork: failed to fork", errno)),
+            }
+            enter_child(shell, fds);
+            vec![]
+        },
+        false => apply_fds(shell, fds)?,
     };
-    let mut should_exit = false;
-    let should_fork = !is_builtin(&cmd_name) || in_subshell;
-    let mut stdin_pre = None;
-    let mut stdout_pre = None;
+
+    match body {
+        CompoundCommand::BraceGroup(list) | CompoundCommand::Subshell(list) => exec_list(shell, list),
+        CompoundCommand::If { branches, else_body } => exec_if(shell, branches, else_body.as_ref()),
+        CompoundCommand::While { cond, body, until } => exec_while(shell, cond, body, *until),
+        CompoundCommand::For { name, words, body } => exec_for(shell, name, words.as_deref(), body),
+        CompoundCommand::Case { word, items } => exec_case(shell, word, items),
+        CompoundCommand::Arith(expr) => exec_arith(shell, expr),
+        CompoundCommand::Cond(expr) => exec_cond_expr(shell, expr),
+    }
 
     if should_fork {
-        match forker.fork() {
-            Ok(is_child) => if !is_child { return Ok(false) },
-            Err(_) => return Err("fork: failed to fork".into()),
+        // child process exits here
+        unsafe { libc::_exit(shell.last_status); }
+    }
+
+    restore_fds(shell, fds_pre);
+    Ok(Some(shell.last_status))
+}
+
+/// Executes the condition of `if`, `while` or `until`, whose failure doesn't trigger `set -e`
+fn exec_cond(shell: &mut Shell, cond: &CommandList) {
+    shell.cond_depth += 1;
+    exec_list(shell, cond);
+    shell.cond_depth -= 1;
+}
+
+/// The status is the one of the executed branch, or 0 if none is executed.
+fn exec_if(shell: &mut Shell, branches: &[(CommandList, CommandList)], else_body: Option<&CommandList>) {
+    for (cond, body) in branches {
+        exec_cond(shell, cond);
+        if shell.flow.is_some() {
+            return;
+        }
+        if shell.last_status == 0 {
+            exec_list(shell, body);
+            return;
         }
     }
+    match else_body {
+        Some(body) => exec_list(shell, body),
+        None => shell.last_status = 0,
+    }
+}
+
+/// The status is the one of the last executed body, or 0 if none is executed.
+fn exec_while(shell: &mut Shell, cond: &CommandList, body: &CommandList, until: bool) {
+    let mut status = 0;
+    shell.loop_depth += 1;
 
-    // replace stdin/stdout and preserve it if needed
-    if let Some(fd) = fd_in {
-        if !should_fork {
-            preserve_fd!(stdin_pre, in);
+    loop {
+        exec_cond(shell, cond);
+        if shell.flow.is_none() {
+            if (shell.last_status == 0) == until {
+                break;
+            }
+            exec_list(shell, body);
+            status = shell.last_status;
+        }
+        if !should_continue(shell) {
+            break;
         }
-        redirect!(fd, in);
     }
-    if let Some(fd) = fd_out {
-        if !should_fork {
-            preserve_fd!(stdout_pre, out);
+
+    shell.loop_depth -= 1;
+    shell.last_status = status;
+}
+
+/// Sets the variable to each word in turn, or each positional parameter if `words` is `None`,
+/// and executes the body.
+fn exec_for(shell: &mut Shell, name: &str, words: Option<&[Word]>, body: &CommandList) {
+    let items = match words {
+        Some(words) => match expand_words(shell, words) {
+            Ok(items) => items,
+            Err(err) => return expansion_error(shell, err),
+        },
+        None => shell.positional.clone(),
+    };
+
+    let mut status = 0;
+    shell.loop_depth += 1;
+
+    for item in items {
+        shell.vars.set(name, item);
+        exec_list(shell, body);
+        status = shell.last_status;
+        if !should_continue(shell) {
+            break;
+        }
+    }
+
+    shell.loop_depth -= 1;
+    shell.last_status = status;
+}
+
+/// Executes the list of the first item with a pattern matching the word, and the following ones for `;&` or `;;&`
+///
+/// The status is the one of the last executed list, or 0 if none is executed.
+fn exec_case(shell: &mut Shell, word: &Word, items: &[CaseItem]) {
+    let subject = match expand_word(shell, word) {
+        Ok(subject) => subject,
+        Err(err) => return expansion_error(shell, err),
+    };
+    shell.last_status = 0;
+    // whether the previous list is followed by `;&`
+    let mut falls_through = false;
+
+    for item in items {
+        if !falls_through {
+            let mut matched = false;
+            for pattern in &item.patterns {
+                match expand_pattern(shell, pattern) {
+                    Ok(pattern) if glob::matches(&pattern, &subject) => {
+                        matched = true;
+                        break;
+                    },
+                    Ok(_) => {},
+                    Err(err) => return expansion_error(shell, err),
+                }
+            }
+            if !matched {
+                continue;
+            }
+        }
+
+        shell.last_status = 0;
+        exec_list(shell, &item.body);
+        if shell.flow.is_some() {
+            return;
+        }
+        match item.terminator {
+            CaseTerminator::Break => return,
+            CaseTerminator::FallThrough => falls_through = true,
+            CaseTerminator::Continue => falls_through = false,
         }
-        redirect!(fd, out);
     }
+}
 
-    let res = match cmd_name.as_str() {
-        "exit" => {
-            should_exit = true;
-            Ok(())
+/// The status is 0 if the value of the expression is non-zero, or 1 otherwise.
+fn exec_arith(shell: &mut Shell, expr: &Word) {
+    let text = match expand_word(shell, expr) {
+        Ok(text) => text,
+        Err(err) => return expansion_error(shell, err),
+    };
+    if shell.opts.xtrace {
+        shell.print_error(format_args!("{}(( {text} ))", shell.vars.get("PS4").unwrap_or("+ ")));
+    }
+    shell.last_status = match arith::eval(shell, &text) {
+        Ok(value) => (value == 0) as i32,
+        Err(err) => {
+            shell.print_error(format_args!("shell: {err}"));
+            1
         },
-        "echo" => builtins::echo(&cmd.args),
-        "cd" => builtins::cd(&cmd.args),
-        "pwd" => builtins::pwd(&cmd.args),
-        _ => exec::exec(&cmd.args),
     };
+}
 
-    if should_fork {
-        if let Some(err) = res.err() {
-            eprintln!("shell: {cmd_name}: {err}");
+/// The status is 0 if the expression is true, 1 if false, or 2 on error.
+fn exec_cond_expr(shell: &mut Shell, expr: &CondExpr) {
+    shell.last_status = match cond::eval(shell, expr) {
+        Ok(value) => !value as i32,
+        Err(err) => {
+            shell.print_error(format_args!("shell: {err}"));
+            2
+        },
+    };
+}
+
+/// Reports the error in expanding the words of a compound command, on which a non-interactive shell exits
+fn expansion_error(shell: &mut Shell, err: ShellError) {
+    shell.print_error(format_args!("shell: {err}"));
+    if !shell.interactive {
+        shell.flow = Some(Flow::Exit(err.status()));
+    }
+    shell.last_status = err.status();
+}
+
+/// Consumes `break` or `continue` for the innermost loop, and returns whether the loop should go on
+fn should_continue(shell: &mut Shell) -> bool {
+    match shell.flow {
+        None => true,
+        Some(Flow::Continue(1)) => {
+            shell.flow = None;
+            true
+        },
+        Some(Flow::Break(1)) => {
+            shell.flow = None;
+            false
+        },
+        // The outer loops are left to continue or break.
+        Some(Flow::Continue(n)) => {
+            shell.flow = Some(Flow::Continue(n - 1));
+            false
+        },
+        Some(Flow::Break(n)) => {
+            shell.flow = Some(Flow::Break(n - 1));
+            false
+        },
+        Some(Flow::Return(_) | Flow::Interrupt | Flow::Exit(_)) => false,
+    }
+}
+
+/// The file descriptors of the shell replaced for a command, along with what `Shell::replace_fd` returned
+type SavedFds = Vec<(RawFd, Option<Option<File>>)>;
+
+/// Replaces the file descriptors of the shell, and returns the original ones to be restored
+fn apply_fds(shell: &mut Shell, fds: Vec<(RawFd, Option<File>)>) -> Result<SavedFds, ShellError> {
+    let mut fds_pre = vec![];
+    for (fd, file) in fds {
+        match shell.replace_fd(fd, file) {
+            Ok(fd_pre) => fds_pre.push((fd, fd_pre)),
+            Err(err) => {
+                restore_fds(shell, fds_pre);
+                let msg = format!("dup: failed to replace fd {fd}");
+                return Err(ShellError::Internal { msg, source: Some(err) });
+            },
         }
-        // child process exits here
-        unsafe { libc::_exit(0); }
     }
+    Ok(fds_pre)
+}
 
-    // restore stdin/stdout
-    if let Some(fd) = stdin_pre {
-        restore_fd!(fd, in);
+/// Replaces the file descriptors of the forked child with the shell's ones along with `fds`,
+/// and enters the working directory of the shell, so that the programs it executes inherit them
+///
+/// The child exits on error.
+fn enter_child(shell: &mut Shell, fds: Vec<(RawFd, Option<File>)>) {
+    shell.job_control = false;
+    let res = apply_fds(shell, fds).and_then(|_| {
+        // The files are moved above the file descriptors they replace, so that none of them is overwritten early.
+        let min_fd = shell.fds.keys().max().map_or(MIN_SHELL_FD, |&fd| MIN_SHELL_FD.max(fd + 1));
+        let fds = std::mem::take(&mut shell.fds).into_iter()
+            .map(|(fd, file)| match file {
+                Some(file) => Ok((fd, redirect::preserve(file.as_raw_fd(), min_fd)?)),
+                None => Ok((fd, None)),
+            })
+            .collect::<Result<Vec<_>, ShellError>>()?;
+        for (fd, file) in fds {
+            redirect::redirect(fd, file.as_ref())?;
+        }
+        env::set_current_dir(&shell.cwd).map_err(|source| {
+            let msg = format!("failed to enter {}", shell.cwd.display());
+            ShellError::Internal { msg, source: Some(source) }
+        })
+    });
+    if let Err(err) = res {
+        shell.print_error(format_args!("shell: {err}"));
+        unsafe { libc::_exit(err.status()); }
     }
-    if let Some(fd) = stdout_pre {
-        restore_fd!(fd, out);
+}
+
+/// Executes the builtin, or the program found in `$PATH` otherwise, which never returns on success
+fn exec_builtin_or_program(shell: &mut Shell, args: &[String]) -> (i32, Option<ShellError>) {
+    match args[0].as_str() {
+        "exit" => builtin_status(builtins::exit(shell, args)),
+        "echo" => builtin_status(builtins::echo(shell, args)),
+        "cd" => builtin_status(builtins::cd(shell, args)),
+        "pwd" => builtin_status(builtins::pwd(shell, args)),
+        "jobs" => builtin_status(builtins::jobs(shell, args)),
+        "fg" => builtin_status(builtins::fg(shell, args)),
+        "bg" => builtin_status(builtins::bg(shell, args)),
+        "wait" => builtin_status(builtins::wait(shell, args)),
+        "export" => builtin_status(builtins::export(shell, args)),
+        "unset" => builtin_status(builtins::unset(shell, args)),
+        "set" => builtin_status(builtins::set(shell, args)),
+        "shopt" => builtin_status(builtins::shopt(shell, args)),
+        "shift" => builtin_status(builtins::shift(shell, args)),
+        "break" => builtin_status(builtins::break_loop(shell, args)),
+        "continue" => builtin_status(builtins::continue_loop(shell, args)),
+        "return" => builtin_status(builtins::r#return(shell, args)),
+        "local" => builtin_status(builtins::local(shell, args)),
+        "complete" => builtin_status(builtins::complete(shell, args)),
+        "source" | "." => builtin_status(builtins::source(shell, args)),
+        "alias" => builtin_status(builtins::alias(shell, args)),
+        "unalias" => builtin_status(builtins::unalias(shell, args)),
+        // An invalid expression is a usage error, which is distinguished from a false one by the status.
+        "test" | "[" => builtin_status(builtins::test(shell, args)),
+        _ => match shell.builtins.get(&args[0]).cloned() {
+            Some(builtin) => builtin_status(builtin(shell, args)),
+            None => {
+                let path_var = shell.vars.get("PATH").unwrap_or_default().to_string();
+                let err = exec::exec(args, &path_var, &shell.vars.environ());
+                (err.status(), Some(err))
+            },
+        },
     }
+}
 
-    if let Some(err) = res.err() {
-        return Err(format!("{cmd_name}: {err}").into());
+/// Restores the file descriptors replaced for a builtin in the reverse order
+fn restore_fds(shell: &mut Shell, fds_pre: SavedFds) {
+    for (fd, fd_pre) in fds_pre.into_iter().rev() {
+        shell.restore_fd(fd, fd_pre);
     }
-    Ok(should_exit)
 }
@@ -1,48 +1,220 @@
-use std::{fs::File, io::{BufReader, Read, Write}, os::fd::OwnedFd};
-
-macro_rules! preserve_fd {
-    ($fd_pre: ident, $dir: ident) => {
-        paste::paste! {
-            match nix::unistd::dup(
-                <std::io::[<Std $dir>] as std::os::unix::io::AsFd>::as_fd(&std::io::[<std $dir>]())
-            ) {
-                Ok(fd) => $fd_pre = Some(fd),
-                Err(_) => return Err(concat!("dup: failed to duplicate std", stringify!($dir)).into()),
-            }
+use nix::{errno::Errno, unistd::pipe};
+use std::{fs::{File, OpenOptions}, io::{self, BufReader, Read, Write}, os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd}};
+
+use crate::executor::forker::Forker;
+use crate::expand::{expand_path, expand_word};
+use crate::shell::{Shell, MIN_SHELL_FD};
+use crate::types::{command::{Redirect, RedirectMode}, error::ShellError};
+
+#[derive(PartialEq)]
+enum Direction {
+    Input,
+    Output,
+}
+
+/// What a file descriptor of a command is redirected to
+enum FdTarget {
+    /// files redirected in the same direction, which are merged or spread if there're more than one
+    Files(Vec<File>, Direction),
+    File(File),
+    Closed,
+}
+
+/// Resolves the redirections of a command from left to right on top of the pipes connecting it,
+/// and returns the file descriptors to be replaced, where `None` means to close it.
+///
+/// The mergers and spreaders are forked with `forker` if needed.
+pub fn resolve(
+    shell: &mut Shell,
+    redirects: &[Redirect],
+    pipe_in: Option<File>,
+    pipe_out: Option<File>,
+    forker: &mut Forker,
+) -> Result<Vec<(RawFd, Option<File>)>, ShellError> {
+    let mut targets: Vec<(RawFd, FdTarget)> = vec![];
+    if let Some(file) = pipe_in {
+        targets.push((0, FdTarget::Files(vec![file], Direction::Input)));
+    }
+    if let Some(file) = pipe_out {
+        targets.push((1, FdTarget::Files(vec![file], Direction::Output)));
+    }
+
+    for redirect in redirects {
+        let fd = redirect.fd;
+        let target = match redirect.mode {
+            RedirectMode::Read | RedirectMode::Write | RedirectMode::Append
+            | RedirectMode::HereDoc | RedirectMode::HereString => {
+                let (file, direction) = open(shell, redirect, forker)?;
+
+                // a file in the same direction as the previous ones is added to them
+                if let Some((_, FdTarget::Files(files, prev_direction))) = targets.iter_mut().find(|(t, _)| *t == fd)
+                    && *prev_direction == direction {
+                    files.push(file);
+                    continue;
+                }
+                FdTarget::Files(vec![file], direction)
+            },
+            RedirectMode::ReadWrite => {
+                let path = expand_path(shell, &redirect.target)?;
+                let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(shell.path(&path))
+                    .map_err(|source| ShellError::Redirect { target: path, source })?;
+                FdTarget::File(file)
+            },
+            RedirectMode::Dup => {
+                let src = expand_word(shell, &redirect.target)?;
+                let Ok(src) = src.parse() else {
+                    return Err(ShellError::Redirect { target: src, source: io::Error::other("invalid file descriptor") });
+                };
+                FdTarget::File(dup_target(shell, &mut targets, src, forker)?)
+            },
+            RedirectMode::Close => FdTarget::Closed,
+        };
+
+        match targets.iter_mut().find(|(t, _)| *t == fd) {
+            Some((_, prev_target)) => *prev_target = target,
+            None => targets.push((fd, target)),
         }
     }
+
+    targets.into_iter()
+        .map(|(fd, target)| Ok((fd, materialize(shell, target, forker)?)))
+        .collect()
 }
 
-macro_rules! restore_fd {
-    ($fd_pre: ident, $dir: ident) => {
-        paste::paste! {
-            if let Err(_) = nix::unistd::[<dup2_std $dir>]($fd_pre) {
-                return Err(concat!("dup2_std", stringify!($dir), ": failed to restore std", stringify!($dir)).into());
-            }
+/// Opens the file to be read or written, where a here-document is read from a pipe
+fn open(shell: &mut Shell, redirect: &Redirect, forker: &mut Forker) -> Result<(File, Direction), ShellError> {
+    if let RedirectMode::HereDoc | RedirectMode::HereString = redirect.mode {
+        let mut content = expand_word(shell, &redirect.target)?;
+        if let RedirectMode::HereString = redirect.mode {
+            content.push('\n');
         }
+        return Ok((here_file(content, forker)?, Direction::Input));
+    }
+
+    let path = expand_path(shell, &redirect.target)?;
+    let full_path = shell.path(&path);
+    let (file, direction) = match redirect.mode {
+        RedirectMode::Read => (File::open(full_path), Direction::Input),
+        RedirectMode::Write => (File::create(full_path), Direction::Output),
+        _ => (OpenOptions::new().append(true).create(true).open(full_path), Direction::Output),
     };
+    Ok((file.map_err(|source| ShellError::Redirect { target: path, source })?, direction))
 }
 
-macro_rules! redirect {
-    ($file: ident, $dir: ident) => {
-        paste::paste! {
-            if let Err(_) = nix::unistd::[<dup2_std $dir>](
-                // <std::os::unix::io::OwnedFd as std::os::unix::io::AsFd>::as_fd(&$fd)
-                $file.as_fd()
-            ) {
-                return Err(concat!("dup2_std", stringify!($dir), ": failed to replace std", stringify!($dir)).into());
-            }
-        }
+/// Returns a duplicate of what `src` is redirected to so far, or of the shell's own `src` if it isn't redirected
+fn dup_target(shell: &Shell, targets: &mut [(RawFd, FdTarget)], src: RawFd, forker: &mut Forker)
+    -> Result<File, ShellError> {
+    let bad_fd = || ShellError::Redirect { target: src.to_string(), source: io::Error::other("bad file descriptor") };
+
+    match targets.iter_mut().find(|(t, _)| *t == src) {
+        Some((_, target)) => {
+            // The files are merged or spread once, and shared by both file descriptors.
+            let file = materialize(shell, std::mem::replace(target, FdTarget::Closed), forker)?.ok_or_else(bad_fd)?;
+            let dup = file.try_clone()?;
+            *target = FdTarget::File(file);
+            Ok(dup)
+        },
+        None => {
+            let raw_fd = shell.raw_fd(src).ok_or_else(bad_fd)?;
+            preserve(raw_fd, MIN_SHELL_FD)?.map(File::from).ok_or_else(bad_fd)
+        },
+    }
+}
+
+/// Returns the read end of a pipe fed with the content by a forked writer,
+/// which doesn't block even if the content exceeds the capacity of the pipe
+fn here_file(content: String, forker: &mut Forker) -> Result<File, ShellError> {
+    let fds = pipe().map_err(|errno| ShellError::internal("pipe: failed to create the pipe for the here-document", errno))?;
+    match forker.fork() {
+        Ok(true) => {
+            drop(fds.0);
+            let _ = File::from(fds.1).write_all(content.as_bytes());
+            unsafe { libc::_exit(0); }
+        },
+        Ok(false) => Ok(File::from(fds.0)),
+        Err(errno) => Err(ShellError::internal("fork: failed to create the writer of the here-document", errno)),
+    }
+}
+
+/// Turns the target into a single file, forking a merger or a spreader for multiple files
+fn materialize(shell: &Shell, target: FdTarget, forker: &mut Forker) -> Result<Option<File>, ShellError> {
+    let (mut files, direction) = match target {
+        FdTarget::Files(files, direction) => (files, direction),
+        FdTarget::File(file) => return Ok(Some(file)),
+        FdTarget::Closed => return Ok(None),
     };
+    if files.len() == 1 {
+        return Ok(files.pop());
+    }
+
+    match direction {
+        // If there're multiple input sources, merge them.
+        Direction::Input => {
+            let fds = pipe()
+                .map_err(|errno| ShellError::internal("pipe: failed to create the pipe connecting from the merger", errno))?;
+            match forker.fork() {
+                Ok(true) => {
+                    if let Err(err) = merge(&files, fds.1) {
+                        shell.print_error(format_args!("shell: merge: failed with an error: {err}"));
+                    }
+                    unsafe { libc::_exit(0); }
+                },
+                Ok(false) => Ok(Some(File::from(fds.0))),
+                Err(errno) => Err(ShellError::internal("fork: failed to create the merger", errno)),
+            }
+        },
+        // If there're multiple output destinations, spread to them.
+        Direction::Output => {
+            let fds = pipe()
+                .map_err(|errno| ShellError::internal("pipe: failed to create the pipe connecting to the spreader", errno))?;
+            match forker.fork() {
+                Ok(true) => {
+                    // The pipe's write end should be closed in the child process
+                    // before spread() starts reading from the pipe's read end.
+                    drop(fds.1);
+                    if let Err(err) = spread(&mut files, fds.0) {
+                        shell.print_error(format_args!("shell: spread: failed with an error: {err}"));
+                    }
+                    unsafe { libc::_exit(0); }
+                },
+                Ok(false) => Ok(Some(File::from(fds.1))),
+                Err(errno) => Err(ShellError::internal("fork: failed to create the spreader", errno)),
+            }
+        },
+    }
 }
 
-/// ```
+/// Duplicates the file descriptor to the lowest one not less than `min_fd`, e.g., out of the way of the users' ones,
+/// or returns `None` if it isn't open
+pub fn preserve(fd: RawFd, min_fd: RawFd) -> Result<Option<OwnedFd>, ShellError> {
+    match Errno::result(unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, min_fd) }) {
+        Ok(dup) => Ok(Some(unsafe { OwnedFd::from_raw_fd(dup) })),
+        Err(Errno::EBADF) => Ok(None),
+        Err(errno) => Err(ShellError::internal(&format!("dup: failed to duplicate fd {fd}"), errno)),
+    }
+}
+
+/// Replaces the file descriptor with `file`, or closes it if `file` is `None`
+pub fn redirect(fd: RawFd, file: Option<&impl AsRawFd>) -> Result<(), ShellError> {
+    match file {
+        Some(file) => match Errno::result(unsafe { libc::dup2(file.as_raw_fd(), fd) }) {
+            Ok(_) => Ok(()),
+            Err(errno) => Err(ShellError::internal(&format!("dup2: failed to replace fd {fd}"), errno)),
+        },
+        None => {
+            unsafe { libc::close(fd); }
+            Ok(())
+        },
+    }
+}
+
+/// ```text
 /// files[0]   ─┐
 ///   ...      ─┼─> fd_dst
 /// files[n-1] ─┘
 /// ```
 /// The function has a cat-like behavior.
-pub fn merge(files: &Vec<File>, fd_dst: OwnedFd) -> Result<(), std::io::Error> {
+pub fn merge(files: &[File], fd_dst: OwnedFd) -> Result<(), io::Error> {
     let mut buf = [0u8; 4096];
     let mut file_dst = File::from(fd_dst);
 
@@ -60,13 +232,13 @@ pub fn merge(files: &Vec<File>, fd_dst: OwnedFd) -> Result<(), std::io::Error> {
     Ok(())
 }
 
-/// ```
+/// ```text
 ///         ┌─> files[0]
 /// fd_src ─┼─>   ...
 ///         └─> files[n-1]
 /// ```
 /// The function has a tee-like behavior.
-pub fn spread(files: &mut Vec<File>, fd_src: OwnedFd) -> Result<(), std::io::Error> {
+pub fn spread(files: &mut [File], fd_src: OwnedFd) -> Result<(), io::Error> {
     let mut buf = [0u8; 4096];
     let mut reader = BufReader::new(File::from(fd_src));
 
@@ -74,7 +246,7 @@ pub fn spread(files: &mut Vec<File>, fd_src: OwnedFd) -> Result<(), std::io::Err
         if n == 0 {
             break;
         }
-        for f in files.into_iter() {
+        for f in files.iter_mut() {
             f.write_all(&buf[..n])?;
         }
     }
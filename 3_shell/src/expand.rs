@@ -0,0 +1,355 @@
+use std::io;
+
+use crate::arith;
+use crate::executor::exec_captured;
+use crate::glob;
+use crate::regex;
+use crate::shell::Shell;
+use crate::types::command::CommandList;
+use crate::types::error::ShellError;
+use crate::types::word::{is_valid_name, Param, ParamOp, Word, WordPart};
+
+const DEFAULT_IFS: &str = " \t\n";
+
+/// A field along with the pattern it is globbed with
+#[derive(Default)]
+struct Field {
+    text: String,
+    /// `text` in which the quoted special characters are escaped
+    pattern: String,
+    /// whether it contains an unquoted `*`, `?` or `[`
+    has_glob: bool,
+}
+
+impl Field {
+    fn push(&mut self, ch: char, quoted: bool) {
+        self.text.push(ch);
+        // A backslash left by the parser or given by an expansion matches itself rather than escaping the next one.
+        if (quoted || ch == '\\') && glob::is_special(ch) {
+            self.pattern.push('\\');
+        } else if matches!(ch, '*' | '?' | '[') {
+            self.has_glob = true;
+        }
+        self.pattern.push(ch);
+    }
+}
+
+/// Fields being built from the parts of a word
+struct Fields {
+    fields: Vec<Field>,
+    current: Field,
+    /// whether `current` is a field even if it is empty, e.g., `""`
+    has_current: bool,
+    /// whether the last field is ended by IFS whitespace,
+    /// in which case an adjacent non-whitespace IFS character doesn't delimit an empty field
+    ended_by_whitespace: bool,
+}
+
+impl Fields {
+    fn new() -> Self {
+        Self { fields: vec![], current: Field::default(), has_current: false, ended_by_whitespace: false }
+    }
+
+    fn push_str(&mut self, s: &str, quoted: bool) {
+        for ch in s.chars() {
+            self.current.push(ch, quoted);
+        }
+        self.has_current = true;
+        self.ended_by_whitespace = false;
+    }
+
+    fn end_field(&mut self) {
+        self.fields.push(std::mem::take(&mut self.current));
+        self.has_current = false;
+    }
+
+    /// Appends the result of an unquoted expansion, which is split by the characters in `ifs`
+    fn push_split(&mut self, s: &str, ifs: &str) {
+        for ch in s.chars() {
+            if !ifs.contains(ch) {
+                self.current.push(ch, false);
+                self.has_current = true;
+                self.ended_by_whitespace = false;
+            } else if ch.is_whitespace() {
+                if self.has_current {
+                    self.end_field();
+                    self.ended_by_whitespace = true;
+                }
+            } else {
+                if self.has_current || !self.ended_by_whitespace {
+                    self.end_field();
+                }
+                self.ended_by_whitespace = false;
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<Field> {
+        if self.has_current {
+            self.end_field();
+        }
+        self.fields
+    }
+}
+
+/// Expands the parts of the word into fields, which are split by `$IFS` only if `split`
+fn expand_fields(shell: &mut Shell, word: &Word, split: bool) -> Result<Vec<Field>, ShellError> {
+    let mut fields = Fields::new();
+
+    for part in &word.parts {
+        let (value, quoted) = match part {
+            WordPart::Literal { text, quoted } => {
+                // an unquoted empty literal doesn't make a field
+                if *quoted || !text.is_empty() {
+                    fields.push_str(text, *quoted);
+                }
+                continue;
+            },
+            // Each positional parameter or element makes a separate field for `$@`, `$*`, `"$@"`,
+            // and the same forms of `${NAME[@]}` and `${NAME[*]}`.
+            WordPart::Param { param, quoted } if split && let Some(values) = all_values(shell, param, *quoted) => {
+                let ifs = shell.vars.get("IFS").unwrap_or(DEFAULT_IFS).to_string();
+                for (i, value) in values.iter().enumerate() {
+                    if *quoted {
+                        if i > 0 {
+                            fields.end_field();
+                        }
+                        fields.push_str(value, true);
+                    } else {
+                        if i > 0 && fields.has_current {
+                            fields.end_field();
+                        }
+                        fields.push_split(value, &ifs);
+                    }
+                }
+                continue;
+            },
+            WordPart::Param { param, quoted } => (expand_param(shell, param)?, *quoted),
+            WordPart::CmdSubst { list, quoted } => (expand_cmd_subst(shell, list)?, *quoted),
+            WordPart::Arith { expr, quoted } => (expand_arith(shell, expr)?, *quoted),
+        };
+        if split && !quoted {
+            let ifs = shell.vars.get("IFS").unwrap_or(DEFAULT_IFS).to_string();
+            fields.push_split(&value, &ifs);
+        } else {
+            fields.push_str(&value, quoted);
+        }
+    }
+    Ok(fields.finish())
+}
+
+/// Returns the paths the field matches, or what to do if nothing matches according to `failglob` and `nullglob`
+fn glob_field(shell: &Shell, field: Field) -> Result<Vec<String>, ShellError> {
+    if !field.has_glob {
+        return Ok(vec![field.text]);
+    }
+    let paths = glob::expand(&shell.cwd, &field.pattern);
+    if !paths.is_empty() {
+        Ok(paths)
+    } else if shell.shopts.failglob {
+        Err(ShellError::Expansion(format!("no match: {}", field.text)))
+    } else if shell.shopts.nullglob {
+        Ok(vec![])
+    } else {
+        Ok(vec![field.text])
+    }
+}
+
+/// Expands the words into fields, where the results of unquoted expansions are split by `$IFS`,
+/// and the fields with unquoted glob patterns are replaced with the matching paths.
+pub fn expand_words(shell: &mut Shell, words: &[Word]) -> Result<Vec<String>, ShellError> {
+    let mut fields = vec![];
+
+    for word in words {
+        for field in expand_fields(shell, word, true)? {
+            fields.extend(glob_field(shell, field)?);
+        }
+    }
+    Ok(fields)
+}
+
+/// Expands the word into a path to redirect to, which has to match exactly one file if it is a glob pattern
+pub fn expand_path(shell: &mut Shell, word: &Word) -> Result<String, ShellError> {
+    let Some(field) = expand_fields(shell, word, false)?.pop() else {
+        return Ok(String::new());
+    };
+    let text = field.text.clone();
+    let mut paths = glob_field(shell, field)?;
+    match paths.len() {
+        1 => Ok(paths.remove(0)),
+        // `nullglob` leaves nothing to redirect to
+        _ => Err(ShellError::Redirect { target: text, source: io::Error::other("ambiguous redirect") }),
+    }
+}
+
+/// Expands the word into a glob pattern without field splitting, where the quoted characters match themselves,
+/// e.g., for a pattern of `case`
+pub fn expand_pattern(shell: &mut Shell, word: &Word) -> Result<String, ShellError> {
+    Ok(expand_fields(shell, word, false)?.pop().map(|field| field.pattern).unwrap_or_default())
+}
+
+/// Expands the word into a regular expression without field splitting, where the quoted characters match themselves,
+/// e.g., for the right side of `=~`
+pub fn expand_regex(shell: &mut Shell, word: &Word) -> Result<String, ShellError> {
+    let mut s = String::new();
+
+    for part in &word.parts {
+        let (value, quoted) = match part {
+            WordPart::Literal { text, quoted } => (text.clone(), *quoted),
+            WordPart::Param { param, quoted } => (expand_param(shell, param)?, *quoted),
+            WordPart::CmdSubst { list, quoted } => (expand_cmd_subst(shell, list)?, *quoted),
+            WordPart::Arith { expr, quoted } => (expand_arith(shell, expr)?, *quoted),
+        };
+        match quoted {
+            true => s.push_str(&regex::escape(&value)),
+            false => s.push_str(&value),
+        }
+    }
+    Ok(s)
+}
+
+/// Expands the word into a single string without field splitting, e.g., for the value of an assignment
+pub fn expand_word(shell: &mut Shell, word: &Word) -> Result<String, ShellError> {
+    let mut s = String::new();
+
+    for part in &word.parts {
+        match part {
+            WordPart::Literal { text, .. } => s.push_str(text),
+            WordPart::Param { param, .. } => s.push_str(&expand_param(shell, param)?),
+            WordPart::CmdSubst { list, .. } => s.push_str(&expand_cmd_subst(shell, list)?),
+            WordPart::Arith { expr, .. } => s.push_str(&expand_arith(shell, expr)?),
+        }
+    }
+    Ok(s)
+}
+
+/// Returns the output of the command list without trailing newlines.
+/// `$?` is set to the exit status of the list.
+fn expand_cmd_subst(shell: &mut Shell, list: &CommandList) -> Result<String, ShellError> {
+    let (mut output, status) = exec_captured(shell, list)?;
+    shell.last_status = status;
+
+    // NUL bytes can't be passed to a program, so they are dropped like in Bash.
+    if output.contains('\0') {
+        shell.print_error("shell: warning: command substitution: ignored null byte in input");
+        output.retain(|ch| ch != '\0');
+    }
+    let trimmed_len = output.trim_end_matches('\n').len();
+    output.truncate(trimmed_len);
+    Ok(output)
+}
+
+/// Returns the value of the arithmetic expression in decimal
+fn expand_arith(shell: &mut Shell, expr: &Word) -> Result<String, ShellError> {
+    let text = expand_word(shell, expr)?;
+    Ok(arith::eval(shell, &text)?.to_string())
+}
+
+/// Returns the positional parameters for `$@` and `$*`, or the elements for `${NAME[@]}` and `${NAME[*]}`,
+/// or `None` for the other parameters or `*`s in double quotes, which are joined into one field
+fn all_values(shell: &Shell, param: &Param, quoted: bool) -> Option<Vec<String>> {
+    let which = param.index.as_deref().unwrap_or(&param.name);
+    if !matches!(param.op, ParamOp::None) || !(which == "@" || (which == "*" && !quoted)) {
+        return None;
+    }
+    match param.index {
+        Some(_) => Some(shell.vars.get_elements(&param.name)),
+        None => Some(shell.positional.clone()),
+    }
+}
+
+/// Joins the values with the first character of `$IFS` like `$*`
+fn join_with_ifs(shell: &Shell, values: &[String]) -> String {
+    let sep = shell.vars.get("IFS").map_or(Some(' '), |ifs| ifs.chars().next());
+    values.join(&sep.map(String::from).unwrap_or_default())
+}
+
+/// Returns the element of `${NAME[index]}`, where a negative index counts from the end, or `None` if it is unset
+///
+/// `@` and `*` give all the elements joined together.
+fn lookup_element(shell: &mut Shell, name: &str, index: &str) -> Result<Option<String>, ShellError> {
+    if shell.vars.get(name).is_none() {
+        return Ok(None);
+    }
+    let elements = shell.vars.get_elements(name);
+    match index {
+        "@" => return Ok(Some(elements.join(" "))),
+        "*" => return Ok(Some(join_with_ifs(shell, &elements))),
+        _ => {},
+    }
+    let index = arith::eval(shell, index)?;
+    let index = match index < 0 {
+        true => elements.len().checked_sub(index.unsigned_abs() as usize),
+        false => Some(index as usize),
+    };
+    Ok(index.and_then(|index| shell.vars.get_element(name, index)).map(str::to_string))
+}
+
+/// Returns the value of the parameter, or `None` if it is unset
+fn lookup(shell: &Shell, name: &str) -> Option<String> {
+    match name {
+        "?" => Some(shell.last_status.to_string()),
+        "$" => Some(shell.pid.to_string()),
+        "!" => shell.last_bg_pid.map(|pid| pid.to_string()),
+        "0" => Some(shell.name.clone()),
+        "#" => Some(shell.positional.len().to_string()),
+        "@" => Some(shell.positional.join(" ")),
+        // joined with the first character of `$IFS`
+        "*" => Some(join_with_ifs(shell, &shell.positional)),
+        _ if name.chars().all(|ch| ch.is_ascii_digit()) => {
+            let n: usize = name.parse().ok()?;
+            shell.positional.get(n.checked_sub(1)?).cloned()
+        },
+        _ => shell.vars.get(name).map(str::to_string),
+    }
+}
+
+fn expand_param(shell: &mut Shell, param: &Param) -> Result<String, ShellError> {
+    let value = match &param.index {
+        Some(index) => lookup_element(shell, &param.name, index)?,
+        None => lookup(shell, &param.name),
+    };
+    // With a colon, an empty value is treated as if it is unset.
+    let is_set = |colon: bool| value.as_ref().is_some_and(|value| !colon || !value.is_empty());
+
+    if shell.opts.nounset && value.is_none() && matches!(param.op, ParamOp::None | ParamOp::Length)
+        && param.name != "@" && param.name != "*" {
+        return Err(ShellError::Expansion(format!("{}: unbound variable", param.name)));
+    }
+
+    match &param.op {
+        ParamOp::None => Ok(value.unwrap_or_default()),
+        // the number of elements for `${#NAME[@]}` and `${#NAME[*]}`
+        ParamOp::Length if matches!(param.index.as_deref(), Some("@" | "*")) => {
+            Ok(shell.vars.get_elements(&param.name).len().to_string())
+        },
+        ParamOp::Length => Ok(value.unwrap_or_default().chars().count().to_string()),
+        ParamOp::Default { word, colon } => match is_set(*colon) {
+            true => Ok(value.unwrap_or_default()),
+            false => expand_word(shell, word),
+        },
+        ParamOp::Assign { word, colon } => match is_set(*colon) {
+            true => Ok(value.unwrap_or_default()),
+            false => {
+                if !is_valid_name(&param.name) || param.index.is_some() {
+                    return Err(ShellError::Expansion(format!("${}: cannot assign in this way", param.name)));
+                }
+                let new_value = expand_word(shell, word)?;
+                shell.vars.set(&param.name, new_value.clone());
+                Ok(new_value)
+            },
+        },
+        ParamOp::Alternative { word, colon } => match is_set(*colon) {
+            true => expand_word(shell, word),
+            false => Ok(String::new()),
+        },
+        ParamOp::Error { word, colon } => match is_set(*colon) {
+            true => Ok(value.unwrap_or_default()),
+            false => {
+                let msg = expand_word(shell, word)?;
+                let msg = if msg.is_empty() { "parameter null or not set".to_string() } else { msg };
+                Err(ShellError::Expansion(format!("{}: {msg}", param.name)))
+            },
+        },
+    }
+}
@@ -0,0 +1,285 @@
+use std::fs;
+use std::path::Path;
+
+/// Characters that have to be escaped with `\` to be matched literally in a pattern
+pub fn is_special(ch: char) -> bool {
+    matches!(ch, '*' | '?' | '[' | ']' | '\\')
+}
+
+enum Token {
+    Char(char),
+    /// `?`
+    Any,
+    /// `*`
+    Star,
+    /// `[...]`, or `[!...]` if negated
+    Class { negated: bool, ranges: Vec<(char, char)>, names: Vec<String> },
+}
+
+/// Returns whether the pattern contains an unescaped `*`, `?` or `[`
+pub fn has_magic(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => { chars.next(); },
+            '*' | '?' | '[' => return true,
+            _ => {},
+        }
+    }
+    false
+}
+
+/// Removes the escaping backslashes
+fn unescape(pattern: &str) -> String {
+    let mut s = String::new();
+    let mut chars = pattern.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => s.extend(chars.next()),
+            _ => s.push(ch),
+        }
+    }
+    s
+}
+
+fn compile(pattern: &str) -> Vec<Token> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                tokens.push(Token::Char(chars[i + 1]));
+                i += 1;
+            },
+            '?' => tokens.push(Token::Any),
+            // consecutive stars are the same as a single one
+            '*' => if !matches!(tokens.last(), Some(Token::Star)) {
+                tokens.push(Token::Star);
+            },
+            '[' => match compile_class(&chars[i + 1..]) {
+                Some((token, len)) => {
+                    tokens.push(token);
+                    i += len;
+                },
+                // an unclosed bracket is matched literally
+                None => tokens.push(Token::Char('[')),
+            },
+            ch => tokens.push(Token::Char(ch)),
+        }
+        i += 1;
+    }
+    tokens
+}
+
+/// Compiles the bracket expression following `[`, and returns it with the number of characters consumed
+fn compile_class(chars: &[char]) -> Option<(Token, usize)> {
+    let mut i = 0;
+    let negated = matches!(chars.first(), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+    let mut ranges = vec![];
+    let mut names = vec![];
+    let start = i;
+
+    loop {
+        let mut ch = *chars.get(i)?;
+        match ch {
+            // `]` right after `[` or `[!` is a member rather than the end
+            ']' if i > start => return Some((Token::Class { negated, ranges, names }, i + 1)),
+            '[' if chars.get(i + 1) == Some(&':') => {
+                let rest: String = chars[i + 2..].iter().collect();
+                if let Some(end) = rest.find(":]") {
+                    let name = &rest[..end];
+                    names.push(name.to_string());
+                    i += 2 + name.chars().count() + 2;
+                    continue;
+                }
+            },
+            '\\' => {
+                i += 1;
+                ch = *chars.get(i)?;
+            },
+            _ => {},
+        }
+        i += 1;
+
+        // `a-z`, where a trailing `-` like `[a-]` is a member
+        if chars.get(i) == Some(&'-') && chars.get(i + 1).is_some_and(|&next| next != ']') {
+            let mut end = chars[i + 1];
+            i += 2;
+            if end == '\\' {
+                end = *chars.get(i)?;
+                i += 1;
+            }
+            ranges.push((ch, end));
+        } else {
+            ranges.push((ch, ch));
+        }
+    }
+}
+
+/// Returns whether the character is in the POSIX character class, e.g., `alpha` for `[:alpha:]`
+pub fn in_named_class(name: &str, ch: char) -> bool {
+    match name {
+        "alnum" => ch.is_alphanumeric(),
+        "alpha" => ch.is_alphabetic(),
+        "blank" => ch == ' ' || ch == '\t',
+        "cntrl" => ch.is_control(),
+        "digit" => ch.is_ascii_digit(),
+        "graph" => ch.is_ascii_graphic(),
+        "lower" => ch.is_lowercase(),
+        "print" => ch.is_ascii_graphic() || ch == ' ',
+        "punct" => ch.is_ascii_punctuation(),
+        "space" => ch.is_whitespace(),
+        "upper" => ch.is_uppercase(),
+        "xdigit" => ch.is_ascii_hexdigit(),
+        _ => false,
+    }
+}
+
+impl Token {
+    fn matches(&self, ch: char) -> bool {
+        match self {
+            Token::Char(expected) => *expected == ch,
+            Token::Any => true,
+            Token::Star => unreachable!(),
+            Token::Class { negated, ranges, names } => {
+                let found = ranges.iter().any(|&(lo, hi)| lo <= ch && ch <= hi)
+                    || names.iter().any(|name| in_named_class(name, ch));
+                found != *negated
+            },
+        }
+    }
+}
+
+/// Returns whether the whole string matches the pattern
+pub fn matches(pattern: &str, s: &str) -> bool {
+    let tokens = compile(pattern);
+    let chars: Vec<char> = s.chars().collect();
+    let (mut t, mut c) = (0, 0);
+    // where to retry if the characters after the last star don't match
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while c < chars.len() {
+        match tokens.get(t) {
+            Some(Token::Star) => {
+                t += 1;
+                backtrack = Some((t, c));
+                continue;
+            },
+            Some(token) if token.matches(chars[c]) => {
+                t += 1;
+                c += 1;
+                continue;
+            },
+            _ => {},
+        }
+        // let the last star consume one more character
+        match backtrack {
+            Some((star_t, star_c)) => {
+                t = star_t;
+                c = star_c + 1;
+                backtrack = Some((star_t, c));
+            },
+            None => return false,
+        }
+    }
+    tokens[t..].iter().all(|token| matches!(token, Token::Star))
+}
+
+/// Returns the sorted paths matching the pattern, where `**` as a whole component matches any levels of directories.
+///
+/// Files beginning with `.` are only matched if the component begins with `.` too.
+/// Relative paths are looked up under `cwd`, but returned as they are.
+pub fn expand(cwd: &Path, pattern: &str) -> Vec<String> {
+    let (prefix, rest) = match pattern.strip_prefix('/') {
+        Some(rest) => ("/", rest),
+        None => ("", pattern),
+    };
+    // a trailing slash only matches directories
+    let dir_only = rest.ends_with('/');
+    let components: Vec<&str> = rest.split('/').filter(|comp| !comp.is_empty()).collect();
+
+    let mut paths = vec![];
+    if !components.is_empty() {
+        walk(cwd, prefix, &components, &mut paths);
+    }
+    if dir_only {
+        paths = paths.into_iter()
+            .filter(|path| cwd.join(path).is_dir())
+            .map(|path| path + "/")
+            .collect();
+    }
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// Returns the names of the entries in the directory, or nothing if it cannot be read
+fn read_dir(cwd: &Path, prefix: &str) -> Vec<String> {
+    match fs::read_dir(cwd.join(prefix)) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// Whether it is a directory to descend into, where symbolic links are not followed to avoid cycles
+fn is_real_dir(cwd: &Path, path: &str) -> bool {
+    fs::symlink_metadata(cwd.join(path)).is_ok_and(|meta| meta.is_dir())
+}
+
+/// Collects the paths under `prefix`, which is empty or ends with `/`, matching the components
+fn walk(cwd: &Path, prefix: &str, components: &[&str], paths: &mut Vec<String>) {
+    let Some((&comp, rest)) = components.split_first() else {
+        return;
+    };
+
+    if comp == "**" {
+        if rest.is_empty() {
+            // matches every file and directory beneath
+            for name in read_dir(cwd, prefix).into_iter().filter(|name| !name.starts_with('.')) {
+                let path = format!("{prefix}{name}");
+                if is_real_dir(cwd, &path) {
+                    walk(cwd, &format!("{path}/"), components, paths);
+                }
+                paths.push(path);
+            }
+        } else {
+            // matches zero or more directories
+            walk(cwd, prefix, rest, paths);
+            for name in read_dir(cwd, prefix).into_iter().filter(|name| !name.starts_with('.')) {
+                let path = format!("{prefix}{name}");
+                if is_real_dir(cwd, &path) {
+                    walk(cwd, &format!("{path}/"), components, paths);
+                }
+            }
+        }
+        return;
+    }
+
+    let names = if has_magic(comp) {
+        let match_hidden = comp.starts_with('.') || comp.starts_with("\\.");
+        read_dir(cwd, prefix).into_iter()
+            .filter(|name| match_hidden || !name.starts_with('.'))
+            .filter(|name| matches(comp, name))
+            .collect()
+    } else {
+        vec![unescape(comp)]
+    };
+
+    for name in names {
+        let path = format!("{prefix}{name}");
+        if rest.is_empty() {
+            if fs::symlink_metadata(cwd.join(&path)).is_ok() {
+                paths.push(path);
+            }
+        } else if cwd.join(&path).is_dir() {
+            walk(cwd, &format!("{path}/"), rest, paths);
+        }
+    }
+}
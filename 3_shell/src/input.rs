@@ -0,0 +1,111 @@
+use nix::{errno::Errno, unistd::read};
+use std::io::{self, Write};
+
+use crate::complete::complete;
+use crate::editor::Editor;
+use crate::parser::parse;
+use crate::prompt;
+use crate::shell::Shell;
+use crate::types::error::ShellError;
+
+/// Where the command lines are read from
+pub enum Input {
+    /// a terminal, where the whole command is edited at once
+    Terminal(Editor),
+    /// stdin, where the prompt is only shown if it is interactive
+    Stdin { interactive: bool },
+    /// a script, or the string given with `-c`, where the name is shown in the errors
+    Text { text: String, offset: usize, name: String },
+}
+
+impl Input {
+    /// The shebang line of a script is left empty, so that the line numbers are kept.
+    pub fn script(mut text: String, name: String) -> Self {
+        if text.starts_with("#!") {
+            let line_len = text.find('\n').unwrap_or(text.len());
+            text.replace_range(..line_len, "");
+        }
+        Input::Text { text, offset: 0, name }
+    }
+
+    pub fn is_interactive(&self) -> bool {
+        matches!(self, Input::Terminal(_) | Input::Stdin { interactive: true })
+    }
+
+    /// Returns where the line is for the errors, e.g., `script.sh: line 3`, which is omitted if interactive
+    pub fn describe_line(&self, line: usize) -> Option<String> {
+        match self {
+            Input::Text { name, .. } => Some(format!("{name}: line {line}")),
+            Input::Stdin { interactive: false } => Some(format!("line {line}")),
+            _ => None,
+        }
+    }
+
+    /// Adds the command to the history if it is read from a terminal
+    pub fn add_history(&mut self, command: &str) {
+        if let Input::Terminal(editor) = self {
+            editor.add_history(command);
+        }
+    }
+
+    /// Shows the prompt given by the variable like `PS1` if interactive,
+    /// and returns the next line including the trailing newline if any.
+    /// A terminal gives the lines up to the end of the command instead, where the continuation lines follow `PS2`.
+    ///
+    /// Returns `Ok(None)` on EOF.
+    pub fn read_line(&mut self, prompt_var: &str, shell: &mut Shell) -> Result<Option<String>, Errno> {
+        match self {
+            Input::Terminal(editor) => editor.read_line(
+                &prompt::expand(shell, prompt_var, default_prompt(prompt_var)),
+                &prompt::expand(shell, "PS2", default_prompt("PS2")),
+                &|text| !matches!(parse(&format!("{text}\n")), Err(ShellError::IncompleteInput { .. })),
+                &mut |text, cursor| complete(shell, text, cursor),
+            ),
+            Input::Stdin { interactive } => {
+                if *interactive {
+                    print!("{}", prompt::printable(&prompt::expand(shell, prompt_var, default_prompt(prompt_var))));
+                    io::stdout().flush().expect("shell: failed to flush");
+                }
+                read_stdin_line()
+            },
+            Input::Text { text, offset, .. } => {
+                let rest = &text[*offset..];
+                if rest.is_empty() {
+                    return Ok(None);
+                }
+                let line_len = rest.find('\n').map_or(rest.len(), |n| n + 1);
+                *offset += line_len;
+                Ok(Some(rest[..line_len].to_string()))
+            },
+        }
+    }
+}
+
+/// Returns the prompt used if the variable like `PS1` is unset
+fn default_prompt(prompt_var: &str) -> &'static str {
+    match prompt_var {
+        "PS1" => "\\s-\\v\\$ ",
+        _ => "> ",
+    }
+}
+
+/// Reads a line from stdin byte by byte,
+/// so that nothing after the line is consumed from a non-seekable input shared with the children.
+fn read_stdin_line() -> Result<Option<String>, Errno> {
+    let mut line = vec![];
+    let mut byte = [0u8];
+
+    loop {
+        match read(io::stdin(), &mut byte)? {
+            0 if line.is_empty() => return Ok(None),
+            0 => break,
+            _ => {
+                line.push(byte[0]);
+                if byte[0] == b'\n' {
+                    break;
+                }
+            },
+        }
+    }
+    Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+}
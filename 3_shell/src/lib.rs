@@ -0,0 +1,143 @@
+use nix::{fcntl::OFlag, unistd::pipe2};
+use std::os::fd::OwnedFd;
+use std::{fs::File, io::Read, rc::Rc, thread};
+
+use crate::executor::exec_list;
+use crate::parser::parse;
+use crate::types::error::{ParseError, ParseErrorKind};
+
+pub use crate::shell::{SetOptions, Shell};
+pub use crate::types::error::ShellError;
+
+pub mod alias;
+pub mod arith;
+pub mod complete;
+pub mod cond;
+pub mod editor;
+pub mod executor;
+pub mod expand;
+pub mod glob;
+pub mod input;
+pub mod parser;
+pub mod prompt;
+pub mod regex;
+pub mod repl;
+pub mod shell;
+pub mod signals;
+pub mod types;
+pub mod variables;
+
+/// The exit status of a command, where 0 means success
+pub type ExitStatus = i32;
+
+/// What the commands evaluated by `Shell::eval_captured` write
+pub struct Output {
+    pub status: ExitStatus,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl Shell {
+    /// Executes the commands in the text, and returns the exit status of the last one
+    ///
+    /// Only a syntax error is returned as an error, in which case nothing is executed.
+    /// The errors of the commands are written to stderr and reflected in the status like in a script,
+    /// and `exit` ends the evaluation rather than the process.
+    pub fn eval(&mut self, text: &str) -> Result<ExitStatus, ShellError> {
+        let text = format!("{text}\n");
+        let list = match parse(&text) {
+            Err(ShellError::IncompleteInput { msg, .. }) => {
+                let end = text.trim_end_matches('\n').len();
+                return Err(ParseError::new(ParseErrorKind::UnexpectedEnd(msg), &text, end..end).into());
+            },
+            res => res?,
+        };
+        exec_list(self, &list);
+        self.flow = None;
+        Ok(self.last_status)
+    }
+
+    /// Executes the commands like `eval`, and returns what they write to stdout and stderr along with the exit status
+    ///
+    /// The background jobs started by the commands write to the buffers too, so it returns after they end.
+    pub fn eval_captured(&mut self, text: &str) -> Result<Output, ShellError> {
+        let (stdout_read, stdout_write) = pipe2(OFlag::O_CLOEXEC)
+            .map_err(|errno| ShellError::internal("pipe: failed to create the pipe capturing stdout", errno))?;
+        let (stderr_read, stderr_write) = pipe2(OFlag::O_CLOEXEC)
+            .map_err(|errno| ShellError::internal("pipe: failed to create the pipe capturing stderr", errno))?;
+        // The pipes are read in other threads, so that the commands don't block on them when they are full.
+        let stdout_reader = thread::spawn(move || read_all(stdout_read));
+        let stderr_reader = thread::spawn(move || read_all(stderr_read));
+
+        let stdout = self.replace_fd(1, Some(File::from(stdout_write)))
+            .map_err(|err| ShellError::Internal { msg: "failed to replace stdout".to_string(), source: Some(err) })?;
+        let stderr = match self.replace_fd(2, Some(File::from(stderr_write))) {
+            Ok(stderr) => stderr,
+            Err(err) => {
+                self.restore_fd(1, stdout);
+                return Err(ShellError::Internal { msg: "failed to replace stderr".to_string(), source: Some(err) });
+            },
+        };
+        let res = self.eval(text);
+        // The write ends are closed, so that the readers get EOF.
+        self.restore_fd(1, stdout);
+        self.restore_fd(2, stderr);
+
+        let stdout = stdout_reader.join().unwrap_or_default();
+        let stderr = stderr_reader.join().unwrap_or_default();
+        res.map(|status| Output { status, stdout, stderr })
+    }
+
+    /// Registers the closure as a builtin, which runs in the shell itself like the other builtins,
+    /// e.g., to set variables
+    ///
+    /// The builtins of the shell itself take precedence over the ones with the same name.
+    pub fn register_builtin(
+        &mut self,
+        name: &str,
+        builtin: impl Fn(&mut Shell, &[String]) -> Result<ExitStatus, ShellError> + 'static,
+    ) {
+        self.builtins.insert(name.to_string(), Rc::new(builtin));
+    }
+}
+
+/// Reads the file up to EOF, and returns what is read even if it fails
+fn read_all(fd: OwnedFd) -> Vec<u8> {
+    let mut buf = vec![];
+    let _ = File::from(fd).read_to_end(&mut buf);
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn output_is_captured_from_builtins_and_programs() {
+        let mut shell = Shell::new(false);
+        shell.register_builtin("greet", |shell, args| {
+            writeln!(shell.stdout(), "hello, {}", args[1])?;
+            Ok(0)
+        });
+        let output = shell.eval_captured("echo out; greet you; sh -c 'echo program'; echo err >&2; false").unwrap();
+        assert_eq!(output.status, 1);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "out\nhello, you\nprogram\n");
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "err\n");
+        assert!(shell.fds.is_empty());
+    }
+
+    #[test]
+    fn each_shell_keeps_its_working_directory() {
+        let process_cwd = std::env::current_dir().unwrap();
+        let mut shell = Shell::new(false);
+        let other = Shell::new(false);
+        shell.eval("cd /").unwrap();
+
+        let output = shell.eval_captured("pwd; sh -c pwd; echo et[c]; [ -d etc ]").unwrap();
+        assert_eq!(output.status, 0);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "/\n/\netc\n");
+        assert_eq!(other.cwd, process_cwd);
+        assert_eq!(std::env::current_dir().unwrap(), process_cwd);
+    }
+}
@@ -1,37 +1,166 @@
-use std::io;
-use std::io::Write;
+use nix::sys::signal::{SigSet, SigmaskHow};
+use nix::unistd::isatty;
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::{io, thread};
 
-use crate::executor::exec_cmds;
-use crate::parser::parse;
+use shell::editor::Editor;
+use shell::input::Input;
+use shell::repl::{run, source};
+use shell::{SetOptions, Shell};
 
-pub mod executor;
-pub mod parser;
-pub mod types;
+/// The stack size of the thread running the commands, which is large enough for deeply nested function calls
+const STACK_SIZE: usize = 256 << 20;
 
-fn main() {
-    loop {
-        print!("> ");
-        io::stdout().flush().expect("shell: failed to flush");
+/// The number of commands kept in the history unless `$HISTSIZE` is set
+const DEFAULT_HISTORY_SIZE: usize = 1000;
+
+const USAGE: &str =
+    "usage: shell [-eulx] [--login] [--norc] [-o OPTION] [-c COMMAND [NAME [ARG]...] | SCRIPT [ARG]...]";
 
-        let mut line = String::new();
-        let bytes_read = io::stdin().read_line(&mut line)
-            .expect("shell: failed to read line");
+/// Executes the file in the shell if it exists
+fn source_if_exists(shell: &mut Shell, path: &Path) {
+    if path.is_file()
+        && let Err(err) = source(shell, path, None) {
+        eprintln!("shell: {err}");
+    }
+}
 
-        if bytes_read == 0 {
-            break;
+/// Reads the startup files, i.e., the profiles for a login shell, or `$SHELL_RC` (`~/.shellrc` by default)
+/// for the other interactive shells unless `--norc` is given
+fn read_startup_files(shell: &mut Shell, login: bool, norc: bool) {
+    let home = shell.vars.get("HOME").map(PathBuf::from);
+    if login {
+        source_if_exists(shell, Path::new("/etc/profile"));
+        // only the first one found
+        let profile = home.iter()
+            .flat_map(|home| [home.join(".shell_profile"), home.join(".profile")])
+            .find(|path| path.is_file());
+        if let Some(profile) = profile {
+            source_if_exists(shell, &profile);
         }
+    } else if shell.interactive && !norc {
+        let rc = match shell.vars.get("SHELL_RC") {
+            Some(path) => Some(PathBuf::from(path)),
+            None => home.map(|home| home.join(".shellrc")),
+        };
+        if let Some(rc) = rc {
+            source_if_exists(shell, &rc);
+        }
+    }
+}
+
+/// Returns `$HISTFILE`, or `~/.shell_history` by default
+fn history_path(shell: &Shell) -> Option<PathBuf> {
+    match shell.vars.get("HISTFILE") {
+        Some(path) => Some(PathBuf::from(path)),
+        None => shell.vars.get("HOME").map(|home| Path::new(home).join(".shell_history")),
+    }
+}
+
+/// Exits with the usage as the shell fails to start
+fn usage_error(msg: &str) -> ! {
+    eprintln!("shell: {msg}");
+    eprintln!("{USAGE}");
+    std::process::exit(2);
+}
+
+/// Parses the arguments and runs the shell, and returns the exit status
+fn start() -> i32 {
+    // Characters are classified by the locale like in other shells, e.g., `.` of `=~` matching `é`.
+    unsafe { libc::setlocale(libc::LC_CTYPE, c"".as_ptr()); }
+    // The arguments that aren't valid UTF-8 are converted lossily except the path of the script.
+    let mut args: VecDeque<OsString> = std::env::args_os().collect();
+    let lossy = |arg: OsString| arg.to_string_lossy().into_owned();
+    let arg0 = args.pop_front().map_or_else(|| "shell".to_string(), lossy);
+    // invoked as a login shell, e.g., `-shell`
+    let mut login = arg0.starts_with('-');
+    let mut norc = false;
 
-        match parse(&line) {
-            Ok(cmds) => {
-                if cmds.is_empty() {
-                    continue;
+    // leading options like `set`
+    let mut opts = SetOptions::default();
+    let mut command = None;
+    while let Some(flags) = args.front()
+        .and_then(|arg| arg.to_str()?.strip_prefix('-'))
+        .filter(|flags| !flags.is_empty()) {
+        let flags = flags.to_string();
+        args.pop_front();
+        match flags.as_str() {
+            "-" => break,
+            "l" | "-login" => login = true,
+            "-norc" => norc = true,
+            "c" => match args.pop_front() {
+                Some(arg) => command = Some(lossy(arg)),
+                None => usage_error("-c: option requires an argument"),
+            },
+            "o" => {
+                let name = args.pop_front().map(lossy).unwrap_or_default();
+                match opts.get_mut(&name) {
+                    Some(opt) => *opt = true,
+                    None => usage_error(&format!("{name}: invalid option name")),
                 }
-                match exec_cmds(cmds) {
-                    Ok(should_exit) => if should_exit { break; },
-                    Err(err) => eprintln!("shell: {err}"),
+            },
+            _ => for flag in flags.chars() {
+                match SetOptions::long_name(flag).and_then(|name| opts.get_mut(name)) {
+                    Some(opt) => *opt = true,
+                    None => usage_error(&format!("-{flag}: invalid option")),
                 }
             },
-            Err(err) => eprintln!("shell: {err}"),
         }
     }
+
+    // `$0` is the script or the name following the command, and the rest are the positional parameters.
+    let (mut input, name) = match command {
+        Some(command) => {
+            let input = Input::Text { text: command, offset: 0, name: "-c".to_string() };
+            (input, args.pop_front().map_or(arg0, lossy))
+        },
+        None => match args.pop_front() {
+            Some(path) => match std::fs::read(&path) {
+                Ok(bytes) => {
+                    let path = lossy(path);
+                    (Input::script(String::from_utf8_lossy(&bytes).into_owned(), path.clone()), path)
+                },
+                Err(err) => {
+                    eprintln!("shell: {}: {err}", path.to_string_lossy());
+                    std::process::exit(if err.kind() == io::ErrorKind::NotFound { 127 } else { 126 });
+                },
+            },
+            None => (Input::Stdin { interactive: isatty(io::stdin()).unwrap_or(false) }, arg0),
+        },
+    };
+
+    let mut shell = Shell::new(input.is_interactive());
+    shell.opts = opts;
+    shell.name = name;
+    shell.positional = args.into_iter().map(lossy).collect();
+
+    read_startup_files(&mut shell, login, norc);
+
+    // Commands typed in a terminal are edited with the line editor, which is set up after the startup files
+    // so that they can set `$HISTFILE` and `$HISTSIZE`.
+    if let Input::Stdin { interactive: true } = input {
+        let size = shell.vars.get("HISTSIZE").and_then(|size| size.parse().ok()).unwrap_or(DEFAULT_HISTORY_SIZE);
+        input = Input::Terminal(Editor::new(history_path(&shell), size));
+    }
+
+    run(&mut shell, &mut input);
+    shell.last_status
+}
+
+fn main() {
+    // The signals are blocked in the main thread, so that they are delivered to the one running the commands,
+    // e.g., to interrupt reading a line.
+    let mask = SigSet::all().thread_swap_mask(SigmaskHow::SIG_BLOCK).ok();
+    let runner = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || {
+            if let Some(mask) = mask {
+                let _ = mask.thread_set_mask();
+            }
+            start()
+        })
+        .expect("shell: failed to spawn the thread running the commands");
+    std::process::exit(runner.join().unwrap_or(1));
 }
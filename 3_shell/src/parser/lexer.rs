@@ -0,0 +1,681 @@
+use std::iter::Peekable;
+
+use crate::parser::{incomplete, parse_nested};
+use crate::types::error::{ShellError, ParseError, ParseErrorKind};
+use crate::types::word::{is_valid_name, Param, ParamOp, Word, WordPart};
+
+/// Operators, where the longer ones come first so that they are matched first
+const OPERATORS: [&str; 22] = [
+    "<<<", "<<-", "&>>", ";;&", "&&", "||", ";;", ";&", "<<", ">>", "<>", "<&", ">&", ">|", "&>",
+    "|", "&", ";", "<", ">", "(", ")",
+];
+
+enum QuoteState {
+    None,
+    InsideSingleQuote,
+    InsideDoubleQuote,
+}
+
+pub enum TokenKind {
+    Word(Word),
+    /// a word of the form `NAME=value`, which is an assignment if it comes before the command name
+    Assignment(Word),
+    /// the digits right before a redirection operator, e.g., `2` in `2>file`
+    IoNumber(i32),
+    Op(&'static str),
+    /// `((expr))`
+    Arith(Word),
+    Newline,
+}
+
+/// A token and its byte offsets in the source text
+pub struct Token {
+    pub kind: TokenKind,
+    pub start: usize,
+    pub end: usize,
+}
+
+struct Lexer {
+    tokens: Vec<Token>,
+    /// the pending word and where it starts
+    word: Word,
+    word_start: usize,
+    /// the indices of the delimiters of the here-documents waiting for their bodies,
+    /// and whether to strip the leading tabs
+    heredocs: Vec<(usize, bool)>,
+}
+
+impl Lexer {
+    /// Adds the pending word ending at `end` if any
+    fn end_word(&mut self, end: usize) {
+        if self.word.is_empty() {
+            return;
+        }
+        // the word following `<<` is the delimiter of a here-document
+        let is_heredoc_delim = match self.tokens.last() {
+            Some(Token { kind: TokenKind::Op(op @ ("<<" | "<<-")), .. }) => {
+                self.heredocs.push((self.tokens.len(), *op == "<<-"));
+                true
+            },
+            _ => false,
+        };
+        let word = std::mem::take(&mut self.word);
+        let kind = match !is_heredoc_delim && word.is_assignment() {
+            true => TokenKind::Assignment(word),
+            false => TokenKind::Word(word),
+        };
+        self.tokens.push(Token { kind, start: self.word_start, end });
+    }
+}
+
+/// Splits the text into words and operators, which are separated by blanks,
+/// where a carriage return is also a blank so that the scripts with CRLF line endings work.
+///
+/// A word beginning with `#` begins a comment up to the end of the line.
+///
+/// The bodies of the here-documents are read from the lines following the one of the operators,
+/// and take the place of the delimiters.
+pub fn tokenize(text: &str) -> Result<Vec<Token>, ShellError> {
+    // where the character being read is, which the errors without positions are reported at,
+    // e.g., `$` of a bad substitution
+    let mut pos = 0;
+    read_tokens(text, &mut pos).map_err(|err| match err {
+        ShellError::Parse(err) if err.line == 0 => ParseError::new(err.kind, text, pos..pos + 1).into(),
+        err => err,
+    })
+}
+
+fn read_tokens(text: &str, pos: &mut usize) -> Result<Vec<Token>, ShellError> {
+    let mut lexer = Lexer { tokens: vec![], word: Word::new(), word_start: 0, heredocs: vec![] };
+    let mut quote_state = QuoteState::None;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, ch)) = chars.next() {
+        let quoted = !matches!(quote_state, QuoteState::None);
+        *pos = i;
+
+        match (ch, &quote_state) {
+            ('\'', QuoteState::None) => {
+                quote_state = QuoteState::InsideSingleQuote;
+                lexer.word.begin_quote();
+            },
+            ('\'', QuoteState::InsideSingleQuote) => quote_state = QuoteState::None,
+            ('"', QuoteState::None) => {
+                quote_state = QuoteState::InsideDoubleQuote;
+                lexer.word.begin_quote();
+            },
+            ('"', QuoteState::InsideDoubleQuote) => quote_state = QuoteState::None,
+            ('$' | '`' | '\\', QuoteState::InsideSingleQuote) => lexer.word.push(ch, quoted),
+            // a backslash followed by a newline continues the line
+            ('\\', _) if text[i + 1..].starts_with('\n') || text[i + 1..].starts_with("\r\n") => {
+                chars.next_if(|&(_, ch)| ch == '\r');
+                chars.next();
+                if chars.peek().is_none() {
+                    return Err(incomplete("the line is continued"));
+                }
+            },
+            ('\\', QuoteState::None) => match chars.next() {
+                Some((_, ch)) => lexer.word.push(ch, true),
+                None => lexer.word.push(ch, false),
+            },
+            // Only `$`, `` ` ``, `"` and `\` can be escaped inside double quotes.
+            ('\\', QuoteState::InsideDoubleQuote) => {
+                let ch = chars.next_if(|&(_, ch)| "$`\"\\".contains(ch)).map_or(ch, |(_, ch)| ch);
+                lexer.word.push(ch, true);
+            },
+            ('$', QuoteState::None) if chars.next_if(|&(_, ch)| ch == '\'').is_some() => {
+                let text = parse_ansi_c_quoted(&mut chars)?;
+                lexer.word.begin_quote();
+                for ch in text.chars() {
+                    lexer.word.push(ch, true);
+                }
+            },
+            ('$', _) => match parse_param(&mut chars, quoted)? {
+                Some(part) => lexer.word.push_part(part),
+                None => lexer.word.push(ch, quoted),
+            },
+            ('`', _) => {
+                let part = parse_backquoted(&mut chars, quoted)?;
+                lexer.word.push_part(part);
+            },
+            (' ' | '\t' | '\r', QuoteState::None) => {
+                lexer.end_word(i);
+                lexer.word_start = i + 1;
+            },
+            ('#', QuoteState::None) if lexer.word.is_empty() => {
+                while chars.next_if(|&(_, ch)| ch != '\n').is_some() {}
+            },
+            ('\n', QuoteState::None) => {
+                lexer.end_word(i);
+                lexer.tokens.push(Token { kind: TokenKind::Newline, start: i, end: i + 1 });
+
+                // The bodies of the here-documents on this line follow it.
+                let mut offset = i + 1;
+                for (idx, strip_tabs) in std::mem::take(&mut lexer.heredocs) {
+                    offset = read_heredoc(&mut lexer.tokens[idx], text, offset, strip_tabs)?;
+                }
+                while chars.next_if(|&(j, _)| j < offset).is_some() {}
+                lexer.word_start = offset;
+            },
+            // `((` begins an arithmetic command rather than nested subshells
+            ('(', QuoteState::None) if lexer.word.is_empty() && text[i..].starts_with("((") => {
+                chars.next();
+                let expr = parse_word(&collect_arith_body(&mut chars)?, true)?;
+                let end = chars.peek().map_or(text.len(), |&(j, _)| j);
+                lexer.tokens.push(Token { kind: TokenKind::Arith(expr), start: i, end });
+                lexer.word_start = end;
+            },
+            (_, QuoteState::None) if "|&;<>()".contains(ch) => {
+                let op = *OPERATORS.iter().find(|op| text[i..].starts_with(**op)).expect("no operator is matched");
+                for _ in 1..op.len() {
+                    chars.next();
+                }
+                // digits right before `<` or `>` are the file descriptor, e.g., `2>`
+                let fd = lexer.word.fd_number().filter(|_| op.starts_with(['<', '>']));
+                match fd {
+                    Some(fd) => {
+                        lexer.word = Word::new();
+                        lexer.tokens.push(Token { kind: TokenKind::IoNumber(fd), start: lexer.word_start, end: i });
+                    },
+                    None => lexer.end_word(i),
+                }
+                lexer.tokens.push(Token { kind: TokenKind::Op(op), start: i, end: i + op.len() });
+                lexer.word_start = i + op.len();
+            },
+            _ => lexer.word.push(ch, quoted),
+        }
+    }
+
+    if !matches!(quote_state, QuoteState::None) {
+        return Err(incomplete("unclosed quotes"));
+    }
+    lexer.end_word(text.len());
+    if let Some(&(idx, _)) = lexer.heredocs.first() {
+        let (delim, _) = heredoc_delim(&lexer.tokens[idx]);
+        let msg = format!("here-document is not terminated by `{delim}`");
+        return Err(ShellError::IncompleteInput { msg, delim: Some(delim) });
+    }
+    Ok(lexer.tokens)
+}
+
+/// Returns the delimiter of a here-document and whether any part of it is quoted
+fn heredoc_delim(token: &Token) -> (String, bool) {
+    let mut delim = String::new();
+    let mut quoted = false;
+    if let TokenKind::Word(word) = &token.kind {
+        for part in &word.parts {
+            if let WordPart::Literal { text, quoted: part_quoted } = part {
+                delim.push_str(text);
+                quoted |= part_quoted;
+            }
+        }
+    }
+    (delim, quoted)
+}
+
+/// Replaces the delimiter with the body of the here-document read from the lines beginning at `start`,
+/// and returns the offset right after the body
+fn read_heredoc(token: &mut Token, text: &str, start: usize, strip_tabs: bool) -> Result<usize, ShellError> {
+    let (delim, quoted) = heredoc_delim(token);
+    let mut offset = start;
+    let mut body = String::new();
+
+    loop {
+        let Some(rest) = text.get(offset..).filter(|rest| !rest.is_empty()) else {
+            let msg = format!("here-document is not terminated by `{delim}`");
+            return Err(ShellError::IncompleteInput { msg, delim: Some(delim) });
+        };
+        let line_len = rest.find('\n').map_or(rest.len(), |n| n + 1);
+        let mut line = &rest[..line_len];
+        offset += line_len;
+        if strip_tabs {
+            line = line.trim_start_matches('\t');
+        }
+        if line.trim_end_matches('\n').trim_end_matches('\r') == delim {
+            break;
+        }
+        body.push_str(line);
+    }
+    token.kind = TokenKind::Word(parse_heredoc_body(&body, !quoted)?);
+    Ok(offset)
+}
+
+/// Parses the body of a here-document, where only parameters, command substitutions
+/// and the backslashes before `$`, `` ` ``, `\` and newlines are recognized if `expand`
+fn parse_heredoc_body(body: &str, expand: bool) -> Result<Word, ShellError> {
+    let mut word = Word::new();
+    let mut chars = body.char_indices().peekable();
+
+    while let Some((_, ch)) = chars.next() {
+        match ch {
+            '\\' if expand => match chars.next_if(|&(_, ch)| "$`\\\n".contains(ch)) {
+                Some((_, '\n')) => {},
+                Some((_, ch)) => word.push(ch, true),
+                None => word.push(ch, true),
+            },
+            '$' if expand => match parse_param(&mut chars, true)? {
+                Some(part) => word.push_part(part),
+                None => word.push(ch, true),
+            },
+            '`' if expand => word.push_part(parse_backquoted(&mut chars, true)?),
+            _ => word.push(ch, true),
+        }
+    }
+    Ok(word)
+}
+
+fn is_special_param(ch: char) -> bool {
+    "?$!#@*-".contains(ch) || ch.is_ascii_digit()
+}
+
+/// A parameter is named by a variable name, digits for a positional parameter, or a special character.
+fn is_param_name(name: &str) -> bool {
+    is_valid_name(name)
+        || (!name.is_empty() && name.chars().all(|ch| ch.is_ascii_digit()))
+        || (name.len() == 1 && name.chars().all(is_special_param))
+}
+
+/// Parses a parameter or a command substitution right after `$`,
+/// or returns `None` if the `$` should be taken literally
+fn parse_param<I>(chars: &mut Peekable<I>, quoted: bool) -> Result<Option<WordPart>, ShellError>
+where
+    I: Iterator<Item = (usize, char)>,
+{
+    let name = match chars.peek() {
+        Some((_, '(')) => {
+            chars.next();
+            if chars.next_if(|&(_, ch)| ch == '(').is_some() {
+                let expr = parse_word(&collect_arith_body(chars)?, true)?;
+                return Ok(Some(WordPart::Arith { expr, quoted }));
+            }
+            let list = parse_nested(&collect_subst_body(chars)?)?;
+            return Ok(Some(WordPart::CmdSubst { list, quoted }));
+        },
+        Some((_, '{')) => {
+            chars.next();
+            let param = parse_braced_param(chars, quoted)?;
+            return Ok(Some(WordPart::Param { param, quoted }));
+        },
+        Some(&(_, ch)) if is_special_param(ch) => {
+            chars.next();
+            ch.to_string()
+        },
+        Some(&(_, ch)) if ch.is_ascii_alphabetic() || ch == '_' => {
+            let mut name = String::new();
+            while let Some((_, ch)) = chars.next_if(|&(_, ch)| ch.is_ascii_alphanumeric() || ch == '_') {
+                name.push(ch);
+            }
+            name
+        },
+        _ => return Ok(None),
+    };
+
+    Ok(Some(WordPart::Param { param: Param { name, index: None, op: ParamOp::None }, quoted }))
+}
+
+/// Collects the text up to the `)` matching the `(` of `$(`, which has been consumed
+///
+/// The `)`s after the patterns of `case` are told apart by counting the unquoted `case`s and `esac`s.
+fn collect_subst_body<I>(chars: &mut Peekable<I>) -> Result<String, ShellError>
+where
+    I: Iterator<Item = (usize, char)>,
+{
+    let mut body = String::new();
+    let mut quote_state = QuoteState::None;
+    let mut depth = 0;
+    let mut case_depth = 0;
+    // the unquoted alphabetic run being collected, to find `case` and `esac`
+    let mut bare_word = String::new();
+
+    loop {
+        let Some((_, ch)) = chars.next() else {
+            return Err(incomplete("unclosed `$(`"));
+        };
+        if ch.is_ascii_alphabetic() && matches!(quote_state, QuoteState::None) {
+            bare_word.push(ch);
+        } else {
+            match bare_word.as_str() {
+                "case" => case_depth += 1,
+                "esac" if case_depth > 0 => case_depth -= 1,
+                _ => {},
+            }
+            bare_word.clear();
+        }
+        match (ch, &quote_state) {
+            ('\'', QuoteState::None) => quote_state = QuoteState::InsideSingleQuote,
+            ('\'', QuoteState::InsideSingleQuote) => quote_state = QuoteState::None,
+            ('"', QuoteState::None) => quote_state = QuoteState::InsideDoubleQuote,
+            ('"', QuoteState::InsideDoubleQuote) => quote_state = QuoteState::None,
+            // the escaped character is kept for the nested parser
+            ('\\', QuoteState::None | QuoteState::InsideDoubleQuote) => {
+                body.push(ch);
+                if let Some((_, ch)) = chars.next() {
+                    body.push(ch);
+                }
+                continue;
+            },
+            ('(', QuoteState::None) => depth += 1,
+            (')', QuoteState::None) if depth == 0 && case_depth == 0 => return Ok(body),
+            (')', QuoteState::None) if depth > 0 => depth -= 1,
+            _ => {},
+        }
+        body.push(ch);
+    }
+}
+
+/// Collects the expression up to the `))` matching `$((` or `((`, which has been consumed
+fn collect_arith_body<I>(chars: &mut Peekable<I>) -> Result<String, ShellError>
+where
+    I: Iterator<Item = (usize, char)>,
+{
+    let mut body = String::new();
+    let mut depth = 0;
+
+    loop {
+        let Some((_, ch)) = chars.next() else {
+            return Err(incomplete("unclosed `((`"));
+        };
+        match ch {
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            ')' => match chars.next_if(|&(_, ch)| ch == ')') {
+                Some(_) => return Ok(body),
+                None => return Err(invalid("unmatched `)` in the arithmetic expression".to_string())),
+            },
+            _ => {},
+        }
+        body.push(ch);
+    }
+}
+
+/// Parses the rest of `` `list` `` right after the opening backquote.
+///
+/// Inside backquotes, a backslash followed by `` ` ``, `\\` or `$` is removed,
+/// so that backquotes can be nested.
+fn parse_backquoted<I>(chars: &mut Peekable<I>, quoted: bool) -> Result<WordPart, ShellError>
+where
+    I: Iterator<Item = (usize, char)>,
+{
+    let mut body = String::new();
+
+    loop {
+        match chars.next() {
+            Some((_, '`')) => break,
+            Some((_, '\\')) => match chars.next_if(|&(_, ch)| "`\\$".contains(ch)) {
+                Some((_, ch)) => body.push(ch),
+                None => body.push('\\'),
+            },
+            Some((_, ch)) => body.push(ch),
+            None => return Err(incomplete("unclosed backquote")),
+        }
+    }
+    Ok(WordPart::CmdSubst { list: parse_nested(&body)?, quoted })
+}
+
+/// Parses the rest of `${...}` right after `${`
+fn parse_braced_param<I>(chars: &mut Peekable<I>, quoted: bool) -> Result<Param, ShellError>
+where
+    I: Iterator<Item = (usize, char)>,
+{
+    // collect the text up to the matching `}`
+    let mut body = String::new();
+    let mut quote_state = QuoteState::None;
+    let mut depth = 0;
+
+    loop {
+        let Some((_, ch)) = chars.next() else {
+            return Err(incomplete("unclosed `${`"));
+        };
+        match (ch, &quote_state) {
+            ('\'', QuoteState::None) => quote_state = QuoteState::InsideSingleQuote,
+            ('\'', QuoteState::InsideSingleQuote) => quote_state = QuoteState::None,
+            ('"', QuoteState::None) => quote_state = QuoteState::InsideDoubleQuote,
+            ('"', QuoteState::InsideDoubleQuote) => quote_state = QuoteState::None,
+            // the escaped character is kept for `parse_word`
+            ('\\', QuoteState::None | QuoteState::InsideDoubleQuote) => {
+                body.push(ch);
+                if let Some((_, ch)) = chars.next() {
+                    body.push(ch);
+                }
+                continue;
+            },
+            ('{', QuoteState::None) => depth += 1,
+            ('}', QuoteState::None) if depth == 0 => break,
+            ('}', QuoteState::None) => depth -= 1,
+            _ => {},
+        }
+        body.push(ch);
+    }
+
+    let bad_substitution = || invalid(format!("${{{body}}}: bad substitution"));
+
+    // `${#}` is the number of positional parameters rather than the length of nothing
+    if let Some(name) = body.strip_prefix('#').filter(|name| !name.is_empty()) {
+        let (name, index) = match split_index(name) {
+            Some((name, index, "")) => (name, Some(index)),
+            Some(_) => return Err(bad_substitution()),
+            None => (name, None),
+        };
+        if !is_param_name(name) {
+            return Err(bad_substitution());
+        }
+        return Ok(Param { name: name.to_string(), index: index.map(str::to_string), op: ParamOp::Length });
+    }
+
+    let name_len = match body.chars().next() {
+        Some(ch) if ch.is_ascii_digit() => body.find(|ch: char| !ch.is_ascii_digit()).unwrap_or(body.len()),
+        Some(ch) if is_special_param(ch) => 1,
+        _ => body.find(|ch: char| !ch.is_ascii_alphanumeric() && ch != '_').unwrap_or(body.len()),
+    };
+    let (name, index, rest) = match split_index(&body) {
+        Some((name, index, rest)) if name.len() == name_len => (name, Some(index), rest),
+        _ => {
+            let (name, rest) = body.split_at(name_len);
+            (name, None, rest)
+        },
+    };
+    if name.is_empty() {
+        return Err(bad_substitution());
+    }
+
+    let (colon, rest) = match rest.strip_prefix(':') {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    let mut rest_chars = rest.chars();
+    let op_ch = rest_chars.next();
+    let word = parse_word(rest_chars.as_str(), quoted)?;
+    let op = match op_ch {
+        None if !colon => ParamOp::None,
+        Some('-') => ParamOp::Default { word, colon },
+        Some('=') => ParamOp::Assign { word, colon },
+        Some('+') => ParamOp::Alternative { word, colon },
+        Some('?') => ParamOp::Error { word, colon },
+        _ => return Err(bad_substitution()),
+    };
+    Ok(Param { name: name.to_string(), index: index.map(str::to_string), op })
+}
+
+/// Returns the syntax error, which is located by `tokenize`
+fn invalid(msg: String) -> ShellError {
+    ParseError::unlocated(ParseErrorKind::Invalid(msg)).into()
+}
+
+/// Parses the rest of `$'...'` right after the opening quote, and returns the text with the escapes replaced,
+/// e.g., `\n`, `\t`, `\xHH`, `\uHHHH` and `\u{H...}`
+fn parse_ansi_c_quoted<I>(chars: &mut Peekable<I>) -> Result<String, ShellError>
+where
+    I: Iterator<Item = (usize, char)>,
+{
+    let mut text = String::new();
+    // The string ends at a NUL character like in Bash, since it can't be passed to a program.
+    let mut truncated = false;
+
+    loop {
+        let ch = match chars.next() {
+            Some((_, '\'')) => return Ok(text),
+            Some((_, '\\')) => match chars.next() {
+                Some((_, ch)) => ch,
+                None => break,
+            },
+            Some((_, ch)) => {
+                if !truncated {
+                    text.push(ch);
+                }
+                continue;
+            },
+            None => break,
+        };
+        let code = match ch {
+            'a' => Some(0x07),
+            'b' => Some(0x08),
+            'e' | 'E' => Some(0x1b),
+            'f' => Some(0x0c),
+            'n' => Some(0x0a),
+            'r' => Some(0x0d),
+            't' => Some(0x09),
+            'v' => Some(0x0b),
+            '\\' | '\'' | '"' | '?' => Some(ch as u32),
+            'x' => take_digits(chars, 16, 2),
+            'u' if chars.next_if(|&(_, ch)| ch == '{').is_some() => {
+                let code = take_digits(chars, 16, 8);
+                chars.next_if(|&(_, ch)| ch == '}');
+                code
+            },
+            'u' => take_digits(chars, 16, 4),
+            'U' => take_digits(chars, 16, 8),
+            // a control character, e.g., `\cA`
+            'c' => chars.next().map(|(_, ch)| ch as u32 & 0x1f),
+            // up to three octal digits including this one
+            '0'..='7' => {
+                let mut code = ch.to_digit(8).unwrap_or_default();
+                for _ in 0..2 {
+                    match chars.next_if(|&(_, ch)| ch.is_digit(8)) {
+                        Some((_, digit)) => code = code * 8 + digit.to_digit(8).unwrap_or_default(),
+                        None => break,
+                    }
+                }
+                Some(code)
+            },
+            _ => None,
+        };
+        match code {
+            _ if truncated => {},
+            Some(0) => truncated = true,
+            Some(code) => text.extend(char::from_u32(code)),
+            None => {
+                // an unknown escape is kept as is
+                text.push('\\');
+                text.push(ch);
+            },
+        }
+    }
+    Err(incomplete("unclosed quotes"))
+}
+
+/// Reads up to `max` digits in the radix, and returns their value if any
+fn take_digits<I>(chars: &mut Peekable<I>, radix: u32, max: usize) -> Option<u32>
+where
+    I: Iterator<Item = (usize, char)>,
+{
+    let mut value: Option<u32> = None;
+    for _ in 0..max {
+        let Some((_, ch)) = chars.next_if(|&(_, ch)| ch.is_digit(radix)) else {
+            break;
+        };
+        value = Some(value.unwrap_or(0) * radix + ch.to_digit(radix).unwrap_or_default());
+    }
+    value
+}
+
+/// Splits `NAME[index]rest` into the name, the non-empty index and the rest
+fn split_index(text: &str) -> Option<(&str, &str, &str)> {
+    let (name, rest) = text.split_once('[')?;
+    let (index, rest) = rest.split_once(']')?;
+    (is_valid_name(name) && !index.is_empty()).then_some((name, index, rest))
+}
+
+/// Parses a word nested in a parameter expansion, e.g., `word` in `${NAME:-word}`,
+/// where quotes and parameters are recognized but blanks and operators are not special.
+///
+/// If `in_double_quotes`, the whole word is quoted, so single quotes are taken literally.
+fn parse_word(text: &str, in_double_quotes: bool) -> Result<Word, ShellError> {
+    let mut word = Word::new();
+    let mut quote_state = QuoteState::None;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((_, ch)) = chars.next() {
+        let quoted = in_double_quotes || !matches!(quote_state, QuoteState::None);
+
+        match (ch, &quote_state) {
+            ('\'', QuoteState::None) if !in_double_quotes => {
+                quote_state = QuoteState::InsideSingleQuote;
+                word.begin_quote();
+            },
+            ('\'', QuoteState::InsideSingleQuote) => quote_state = QuoteState::None,
+            ('"', QuoteState::None) => {
+                quote_state = QuoteState::InsideDoubleQuote;
+                word.begin_quote();
+            },
+            ('"', QuoteState::InsideDoubleQuote) => quote_state = QuoteState::None,
+            ('$' | '\\', QuoteState::InsideSingleQuote) => word.push(ch, quoted),
+            // Inside double quotes, only `$`, `` ` ``, `"`, `\` and `}` can be escaped.
+            ('\\', _) => match chars.next_if(|&(_, next)| !quoted || "$`\"\\}".contains(next)) {
+                Some((_, ch)) => word.push(ch, true),
+                None => word.push(ch, quoted),
+            },
+            ('$', QuoteState::None) if !in_double_quotes && chars.next_if(|&(_, ch)| ch == '\'').is_some() => {
+                let text = parse_ansi_c_quoted(&mut chars)?;
+                word.begin_quote();
+                for ch in text.chars() {
+                    word.push(ch, true);
+                }
+            },
+            ('$', _) => match parse_param(&mut chars, quoted)? {
+                Some(part) => word.push_part(part),
+                None => word.push(ch, quoted),
+            },
+            ('`', QuoteState::InsideSingleQuote) => word.push(ch, quoted),
+            ('`', _) => word.push_part(parse_backquoted(&mut chars, quoted)?),
+            _ => word.push(ch, quoted),
+        }
+    }
+    Ok(word)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::word::WordPart;
+
+    /// Returns the text of the literal parts of the only word in the text
+    fn literal(text: &str) -> String {
+        let tokens = tokenize(text).unwrap_or_else(|err| panic!("{err}"));
+        let Some(Token { kind: TokenKind::Word(word), .. }) = tokens.first() else {
+            panic!("no word in {text:?}");
+        };
+        word.parts.iter()
+            .map(|part| match part {
+                WordPart::Literal { text, .. } => text.as_str(),
+                _ => panic!("not a literal in {text:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ansi_c_quoting_replaces_escapes() {
+        assert_eq!(literal("$'a\\tb\\x41\\u263A\\101\\cA'\n"), "a\tbA\u{263A}A\x01");
+    }
+
+    #[test]
+    fn ansi_c_quoting_ends_at_nul() {
+        assert_eq!(literal("$'a\\x00b'c\n"), "ac");
+        assert_eq!(literal("$'a\\0\\'b'c\n"), "ac");
+        assert_eq!(literal("$'a\\u{0}b'\n"), "a");
+    }
+
+    #[test]
+    fn ansi_c_quoting_takes_up_to_three_octal_digits() {
+        assert_eq!(literal("$'\\1x\\0101\\7'\n"), "\x01x\x081\x07");
+    }
+}
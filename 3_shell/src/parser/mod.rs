@@ -0,0 +1,658 @@
+use std::{iter::Peekable, ops::Range, rc::Rc, vec::IntoIter};
+
+use crate::cond::{is_binary_op, is_unary_op};
+use crate::parser::lexer::{tokenize, Token, TokenKind};
+use crate::types::error::{ShellError, ParseError, ParseErrorKind};
+use crate::types::command::{
+    AndOrList, AndOrOp, CaseItem, CaseTerminator, Command, CommandList, CompoundCommand, CondExpr, Pipeline, Redirect,
+    RedirectMode, SimpleCommand,
+};
+use crate::types::word::{is_valid_name, Word, WordPart};
+
+mod lexer;
+
+/// Words that begin or end compound commands when they appear where a command is expected
+const RESERVED_WORDS: [&str; 19] = [
+    "if", "then", "elif", "else", "fi", "while", "until", "for", "do", "done", "case", "esac", "in", "function",
+    "{", "}", "!", "[[", "]]",
+];
+
+/// Returns the parsed `CommandList`
+///
+/// `ShellError::IncompleteInput` is returned if the text ends in the middle of a command,
+/// e.g., inside quotes or an `if` without `fi`, and `ShellError::Parse` is returned for a syntax error.
+pub fn parse(cmd_line: &str) -> Result<CommandList, ShellError> {
+    let mut parser = Parser {
+        text: cmd_line,
+        tokens: tokenize(cmd_line)?.into_iter().peekable(),
+        last_end: 0,
+    };
+    let list = parser.parse_list(&[])?;
+    if parser.tokens.peek().is_some() {
+        return Err(parser.unexpected());
+    }
+    Ok(list)
+}
+
+/// Parses the list nested in a command substitution, which should be complete by itself
+fn parse_nested(text: &str) -> Result<CommandList, ShellError> {
+    parse(text).map_err(|err| match err {
+        // The errors are reported where the substitution begins in the outer text.
+        ShellError::IncompleteInput { msg, .. } => ParseError::unlocated(ParseErrorKind::Invalid(msg)).into(),
+        ShellError::Parse(err) => ParseError::unlocated(ParseErrorKind::Invalid(err.message())).into(),
+        err => err,
+    })
+}
+
+/// Returns how the token is written in the error messages
+fn describe(text: &str, token: &Token) -> String {
+    match token.kind {
+        TokenKind::Newline => "newline".to_string(),
+        _ => text[token.start..token.end].to_string(),
+    }
+}
+
+/// Returns the error for the token, which is not expected there
+fn unexpected_token(text: &str, token: &Token) -> ShellError {
+    let kind = ParseErrorKind::UnexpectedToken(describe(text, token));
+    ParseError::new(kind, text, token.start..token.end).into()
+}
+
+fn incomplete(msg: &str) -> ShellError {
+    ShellError::IncompleteInput { msg: msg.to_string(), delim: None }
+}
+
+/// A recursive descent parser over the tokens
+struct Parser<'a> {
+    text: &'a str,
+    tokens: Peekable<IntoIter<Token>>,
+    /// where the last consumed token ends, which is used to take the source text of the pipelines
+    last_end: usize,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.next()?;
+        self.last_end = token.end;
+        Some(token)
+    }
+
+    fn peek_kind(&mut self) -> Option<&TokenKind> {
+        self.tokens.peek().map(|token| &token.kind)
+    }
+
+    fn peek_op(&mut self) -> Option<&'static str> {
+        match self.peek_kind() {
+            Some(TokenKind::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    /// Returns the next token if it is a reserved word
+    fn peek_reserved(&mut self) -> Option<&'static str> {
+        match self.peek_kind() {
+            Some(TokenKind::Word(word)) => word.as_literal()
+                .and_then(|text| RESERVED_WORDS.iter().find(|reserved| **reserved == text).copied()),
+            _ => None,
+        }
+    }
+
+    /// Where the next token starts
+    fn next_start(&mut self) -> usize {
+        let end = self.text.len();
+        self.tokens.peek().map_or(end, |token| token.start)
+    }
+
+    fn skip_newlines(&mut self) {
+        while let Some(TokenKind::Newline) = self.peek_kind() {
+            self.next();
+        }
+    }
+
+    /// Returns the error for the next token, which is not expected there
+    fn unexpected(&mut self) -> ShellError {
+        let text = self.text;
+        match self.tokens.peek() {
+            Some(token) => unexpected_token(text, token),
+            None => incomplete("unexpected end of input"),
+        }
+    }
+
+    /// Returns the error with the message for the span of the source text
+    fn invalid(&self, msg: String, span: Range<usize>) -> ShellError {
+        ParseError::new(ParseErrorKind::Invalid(msg), self.text, span).into()
+    }
+
+    /// Consumes the reserved word or returns an error
+    fn expect(&mut self, reserved: &str) -> Result<(), ShellError> {
+        if self.peek_reserved() != Some(reserved) {
+            return Err(self.unexpected());
+        }
+        self.next();
+        Ok(())
+    }
+
+    /// Consumes the operator or returns an error
+    fn expect_op(&mut self, op: &str) -> Result<(), ShellError> {
+        if self.peek_op() != Some(op) {
+            return Err(self.unexpected());
+        }
+        self.next();
+        Ok(())
+    }
+
+    /// Parses the and-or lists up to the end of input or one of the `terminators`,
+    /// which are reserved words expected where a command begins, or operators like `)` and `;;`
+    fn parse_list(&mut self, terminators: &[&str]) -> Result<CommandList, ShellError> {
+        let mut items = vec![];
+
+        loop {
+            self.skip_newlines();
+            let at_terminator = match self.peek_kind() {
+                None => true,
+                Some(TokenKind::Op(op)) => terminators.contains(op),
+                _ => self.peek_reserved().is_some_and(|reserved| terminators.contains(&reserved)),
+            };
+            if at_terminator {
+                break;
+            }
+
+            let start = self.next_start();
+            let (first, rest) = self.parse_and_or()?;
+            let text = self.text[start..self.last_end].trim().to_string();
+            let background = match self.peek_kind() {
+                Some(TokenKind::Op("&")) => {
+                    self.next();
+                    true
+                },
+                Some(TokenKind::Op(";")) => {
+                    self.next();
+                    false
+                },
+                None | Some(TokenKind::Newline) => false,
+                Some(TokenKind::Op(op)) if terminators.contains(op) => false,
+                _ => return Err(self.unexpected()),
+            };
+            items.push(AndOrList { first, rest, background, text });
+        }
+        Ok(CommandList { items })
+    }
+
+    /// Parses the list in a compound command, which shouldn't be empty
+    fn parse_compound_list(&mut self, terminators: &[&str]) -> Result<CommandList, ShellError> {
+        let list = self.parse_list(terminators)?;
+        if list.items.is_empty() {
+            return Err(self.unexpected());
+        }
+        Ok(list)
+    }
+
+    fn parse_and_or(&mut self) -> Result<(Pipeline, Vec<(AndOrOp, Pipeline)>), ShellError> {
+        let first = self.parse_pipeline()?;
+        let mut rest = vec![];
+
+        loop {
+            let op = match self.peek_op() {
+                Some("&&") => AndOrOp::And,
+                Some("||") => AndOrOp::Or,
+                _ => break,
+            };
+            self.next();
+            // the next pipeline may be on the following lines
+            self.skip_newlines();
+            rest.push((op, self.parse_pipeline()?));
+        }
+        Ok((first, rest))
+    }
+
+    fn parse_pipeline(&mut self) -> Result<Pipeline, ShellError> {
+        let start = self.next_start();
+        let negated = self.peek_reserved() == Some("!");
+        if negated {
+            self.next();
+        }
+
+        let mut cmds = vec![self.parse_command()?];
+        while self.peek_op() == Some("|") {
+            self.next();
+            // the next command may be on the following lines
+            self.skip_newlines();
+            cmds.push(self.parse_command()?);
+        }
+        let text = self.text[start..self.last_end].trim().to_string();
+        Ok(Pipeline { cmds, negated, text })
+    }
+
+    fn parse_command(&mut self) -> Result<Command, ShellError> {
+        let body = match self.peek_reserved() {
+            Some("{") => {
+                self.next();
+                let list = self.parse_compound_list(&["}"])?;
+                self.expect("}")?;
+                CompoundCommand::BraceGroup(list)
+            },
+            Some("if") => self.parse_if()?,
+            Some(reserved @ ("while" | "until")) => {
+                self.next();
+                let cond = self.parse_compound_list(&["do"])?;
+                let body = self.parse_do_group()?;
+                CompoundCommand::While { cond, body, until: reserved == "until" }
+            },
+            Some("for") => self.parse_for()?,
+            Some("case") => self.parse_case()?,
+            Some("[[") => {
+                self.next();
+                let expr = self.parse_cond_or()?;
+                self.skip_newlines();
+                self.expect("]]")?;
+                CompoundCommand::Cond(expr)
+            },
+            Some("function") => {
+                self.next();
+                let name = match self.next() {
+                    Some(Token { kind: TokenKind::Word(word) | TokenKind::Assignment(word), start, end }) => match word.as_literal() {
+                        Some(name) if is_function_name(name) => name.to_string(),
+                        _ => return Err(self.invalid("function: invalid function name".to_string(), start..end)),
+                    },
+                    Some(token) => return Err(unexpected_token(self.text, &token)),
+                    None => return Err(incomplete("unexpected end of input")),
+                };
+                // `()` is optional after the name
+                if self.peek_op() == Some("(") {
+                    self.next();
+                    self.expect_op(")")?;
+                }
+                return self.parse_function_body(name);
+            },
+            Some(_) => return Err(self.unexpected()),
+            None if self.peek_op() == Some("(") => {
+                self.next();
+                let list = self.parse_compound_list(&[")"])?;
+                self.expect_op(")")?;
+                CompoundCommand::Subshell(list)
+            },
+            None if matches!(self.peek_kind(), Some(TokenKind::Arith(_))) => match self.next() {
+                Some(Token { kind: TokenKind::Arith(expr), .. }) => CompoundCommand::Arith(expr),
+                _ => unreachable!(),
+            },
+            None => {
+                let cmd = self.parse_simple_command()?;
+                // a single word followed by `()` names a function
+                if self.peek_op() == Some("(") && cmd.assigns.is_empty() && cmd.redirects.is_empty()
+                    && let [word] = cmd.args.as_slice()
+                    && let Some(name) = word.as_literal().filter(|name| is_function_name(name)) {
+                    let name = name.to_string();
+                    self.next();
+                    self.expect_op(")")?;
+                    return self.parse_function_body(name);
+                }
+                return Ok(Command::Simple(cmd));
+            },
+        };
+
+        let mut redirects = vec![];
+        while self.at_redirect() {
+            self.parse_redirect(&mut redirects)?;
+        }
+        Ok(Command::Compound { body, redirects })
+    }
+
+    /// Parses the compound command following the name of a function and `()` if any
+    fn parse_function_body(&mut self, name: String) -> Result<Command, ShellError> {
+        self.skip_newlines();
+        let start = self.next_start();
+        match self.parse_command()? {
+            body @ Command::Compound { .. } => Ok(Command::FunctionDef { name, body: Rc::new(body) }),
+            _ => {
+                let msg = format!("{name}: the body of a function should be a compound command");
+                Err(self.invalid(msg, start..self.last_end))
+            },
+        }
+    }
+
+    /// `if list; then list; [elif list; then list;]... [else list;] fi`
+    fn parse_if(&mut self) -> Result<CompoundCommand, ShellError> {
+        let mut branches = vec![];
+        let mut else_body = None;
+
+        // `if` or `elif`
+        while let Some("if" | "elif") = self.peek_reserved() {
+            self.next();
+            let cond = self.parse_compound_list(&["then"])?;
+            self.expect("then")?;
+            let body = self.parse_compound_list(&["elif", "else", "fi"])?;
+            branches.push((cond, body));
+        }
+        if self.peek_reserved() == Some("else") {
+            self.next();
+            else_body = Some(self.parse_compound_list(&["fi"])?);
+        }
+        self.expect("fi")?;
+        Ok(CompoundCommand::If { branches, else_body })
+    }
+
+    /// `for NAME [in WORD...]; do list; done`
+    fn parse_for(&mut self) -> Result<CompoundCommand, ShellError> {
+        self.next();
+        let name = match self.next() {
+            Some(Token { kind: TokenKind::Word(word) | TokenKind::Assignment(word), start, end }) => {
+                match word.as_literal() {
+                    Some(name) if is_valid_name(name) => name.to_string(),
+                    _ => return Err(self.invalid("for: invalid variable name".to_string(), start..end)),
+                }
+            },
+            Some(token) => return Err(unexpected_token(self.text, &token)),
+            None => return Err(incomplete("unexpected end of input")),
+        };
+
+        self.skip_newlines();
+        let mut words = None;
+        if self.peek_reserved() == Some("in") {
+            self.next();
+            let mut list = vec![];
+            while let Some(TokenKind::Word(_) | TokenKind::Assignment(_)) = self.peek_kind() {
+                if let Some(Token { kind: TokenKind::Word(word) | TokenKind::Assignment(word), .. }) = self.next() {
+                    list.push(word);
+                }
+            }
+            words = Some(list);
+        }
+        match self.peek_kind() {
+            Some(TokenKind::Op(";") | TokenKind::Newline) => {
+                self.next();
+            },
+            _ if words.is_some() => return Err(self.unexpected()),
+            _ => {},
+        }
+        self.skip_newlines();
+
+        let body = self.parse_do_group()?;
+        Ok(CompoundCommand::For { name, words, body })
+    }
+
+    /// `case WORD in [(]PATTERN[|PATTERN]...) list;; ... esac`,
+    /// where the list of each item may be empty and the terminator of the last one may be omitted
+    fn parse_case(&mut self) -> Result<CompoundCommand, ShellError> {
+        self.next();
+        let word = self.expect_word()?;
+        self.skip_newlines();
+        self.expect("in")?;
+
+        let mut items = vec![];
+        loop {
+            self.skip_newlines();
+            if self.peek_reserved() == Some("esac") {
+                break;
+            }
+            if self.peek_op() == Some("(") {
+                self.next();
+            }
+            let mut patterns = vec![self.expect_word()?];
+            while self.peek_op() == Some("|") {
+                self.next();
+                patterns.push(self.expect_word()?);
+            }
+            self.expect_op(")")?;
+
+            let body = self.parse_list(&[";;", ";&", ";;&", "esac"])?;
+            let terminator = match self.peek_op() {
+                Some(";&") => CaseTerminator::FallThrough,
+                Some(";;&") => CaseTerminator::Continue,
+                Some(";;") => CaseTerminator::Break,
+                // only the last item may end without a terminator
+                _ => {
+                    items.push(CaseItem { patterns, body, terminator: CaseTerminator::Break });
+                    break;
+                },
+            };
+            self.next();
+            items.push(CaseItem { patterns, body, terminator });
+        }
+        self.expect("esac")?;
+        Ok(CompoundCommand::Case { word, items })
+    }
+
+    /// `expr || expr` in `[[ ]]`, where newlines are allowed between the operands and the operators
+    fn parse_cond_or(&mut self) -> Result<CondExpr, ShellError> {
+        let mut expr = self.parse_cond_and()?;
+        self.skip_newlines();
+        while self.peek_op() == Some("||") {
+            self.next();
+            expr = CondExpr::Or(Box::new(expr), Box::new(self.parse_cond_and()?));
+            self.skip_newlines();
+        }
+        Ok(expr)
+    }
+
+    /// `expr && expr` in `[[ ]]`, which has higher precedence than `||`
+    fn parse_cond_and(&mut self) -> Result<CondExpr, ShellError> {
+        let mut expr = self.parse_cond_not()?;
+        self.skip_newlines();
+        while self.peek_op() == Some("&&") {
+            self.next();
+            expr = CondExpr::And(Box::new(expr), Box::new(self.parse_cond_not()?));
+            self.skip_newlines();
+        }
+        Ok(expr)
+    }
+
+    /// `! expr`, `( expr )`, `-op word`, `word op word` or `word` in `[[ ]]`
+    fn parse_cond_not(&mut self) -> Result<CondExpr, ShellError> {
+        self.skip_newlines();
+        if self.peek_reserved() == Some("!") {
+            self.next();
+            return Ok(CondExpr::Not(Box::new(self.parse_cond_not()?)));
+        }
+        if self.peek_op() == Some("(") {
+            self.next();
+            let expr = self.parse_cond_or()?;
+            self.expect_op(")")?;
+            return Ok(expr);
+        }
+        if self.peek_reserved() == Some("]]") {
+            return Err(self.unexpected());
+        }
+
+        let word = self.expect_word()?;
+        let op = match self.peek_kind() {
+            Some(TokenKind::Op(op @ ("<" | ">"))) => Some(op.to_string()),
+            Some(TokenKind::Word(next)) => next.as_literal()
+                .filter(|next| is_binary_op(next) || *next == "=~")
+                .map(str::to_string),
+            _ => None,
+        };
+        if let Some(op) = op {
+            self.next();
+            let rhs = match op.as_str() {
+                "=~" => self.parse_regex()?,
+                _ => self.parse_cond_operand()?,
+            };
+            return Ok(CondExpr::Binary { op, lhs: word, rhs });
+        }
+        match word.as_literal() {
+            Some(op) if is_unary_op(op) => {
+                let op = op.to_string();
+                let arg = self.parse_cond_operand()?;
+                Ok(CondExpr::Unary { op, arg })
+            },
+            _ => Ok(CondExpr::Word(word)),
+        }
+    }
+
+    /// Consumes a word other than `]]` as an operand in `[[ ]]`
+    fn parse_cond_operand(&mut self) -> Result<Word, ShellError> {
+        if self.peek_reserved() == Some("]]") {
+            return Err(self.unexpected());
+        }
+        self.expect_word()
+    }
+
+    /// Parses the regular expression after `=~`, where the operators like `(` and `|` are taken literally
+    /// as long as they are not separated by blanks and the parentheses are balanced
+    fn parse_regex(&mut self) -> Result<Word, ShellError> {
+        let mut regex = Word::new();
+        let mut depth = 0;
+        let mut end = None;
+
+        while let Some(token) = self.tokens.peek() {
+            if end.is_some_and(|end| token.start != end) {
+                break;
+            }
+            match &token.kind {
+                TokenKind::Word(word) if end.is_some() || word.as_literal() != Some("]]") => {},
+                TokenKind::Assignment(_) => {},
+                TokenKind::Op("(") => depth += 1,
+                TokenKind::Op(")") if depth > 0 => depth -= 1,
+                TokenKind::Op("|" | "<" | ">") => {},
+                _ => break,
+            }
+            let Some(token) = self.next() else {
+                break;
+            };
+            end = Some(token.end);
+            match token.kind {
+                TokenKind::Word(word) | TokenKind::Assignment(word) => regex.parts.extend(word.parts),
+                _ => for ch in self.text[token.start..token.end].chars() {
+                    regex.push(ch, false);
+                },
+            }
+        }
+        if end.is_none() {
+            return Err(self.unexpected());
+        }
+        Ok(regex)
+    }
+
+    /// Consumes a word, which may be a reserved word, or returns an error
+    fn expect_word(&mut self) -> Result<Word, ShellError> {
+        match self.peek_kind() {
+            Some(TokenKind::Word(_) | TokenKind::Assignment(_)) => match self.next() {
+                Some(Token { kind: TokenKind::Word(word) | TokenKind::Assignment(word), .. }) => Ok(word),
+                _ => unreachable!(),
+            },
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    /// `do list; done`
+    fn parse_do_group(&mut self) -> Result<CommandList, ShellError> {
+        self.expect("do")?;
+        let body = self.parse_compound_list(&["done"])?;
+        self.expect("done")?;
+        Ok(body)
+    }
+
+    /// Parses a simple command, where the assignment words before the command name are assignments
+    fn parse_simple_command(&mut self) -> Result<SimpleCommand, ShellError> {
+        let mut assigns = vec![];
+        let mut args = vec![];
+        let mut redirects = vec![];
+
+        loop {
+            if self.at_redirect() {
+                self.parse_redirect(&mut redirects)?;
+                continue;
+            }
+            if !matches!(self.peek_kind(), Some(TokenKind::Word(_) | TokenKind::Assignment(_))) {
+                break;
+            }
+            match self.next().map(|token| token.kind) {
+                Some(TokenKind::Assignment(word)) if args.is_empty() => match word.into_assignment() {
+                    Ok(assign) => assigns.push(assign),
+                    Err(word) => args.push(word),
+                },
+                Some(TokenKind::Word(word) | TokenKind::Assignment(word)) => args.push(word),
+                _ => unreachable!(),
+            }
+        }
+
+        if assigns.is_empty() && args.is_empty() && redirects.is_empty() {
+            let text = self.text;
+            return Err(match self.tokens.peek() {
+                Some(token) => {
+                    let msg = format!("no command is provided before `{}`", describe(text, token));
+                    ParseError::new(ParseErrorKind::Invalid(msg), text, token.start..token.end).into()
+                },
+                None => incomplete("unexpected end of input"),
+            });
+        }
+        Ok(SimpleCommand { assigns, args, redirects })
+    }
+
+    fn at_redirect(&mut self) -> bool {
+        match self.peek_kind() {
+            Some(TokenKind::IoNumber(_)) => true,
+            Some(TokenKind::Op(op)) => op.starts_with(['<', '>']) || op.starts_with("&>"),
+            _ => false,
+        }
+    }
+
+    /// Parses `[N]<op>target`
+    fn parse_redirect(&mut self, redirects: &mut Vec<Redirect>) -> Result<(), ShellError> {
+        let mut fd = None;
+        if let Some(&TokenKind::IoNumber(n)) = self.peek_kind() {
+            self.next();
+            fd = Some(n);
+        }
+        let Some(op) = self.peek_op() else {
+            return Err(self.unexpected());
+        };
+        self.next();
+
+        let (default_fd, mode, with_stderr) = match op {
+            ">" | ">|" => (1, RedirectMode::Write, false),
+            ">>" => (1, RedirectMode::Append, false),
+            ">&" => (1, RedirectMode::Dup, false),
+            "<>" => (0, RedirectMode::ReadWrite, false),
+            "<&" => (0, RedirectMode::Dup, false),
+            "<<" | "<<-" => (0, RedirectMode::HereDoc, false),
+            "<<<" => (0, RedirectMode::HereString, false),
+            // `&>file` or `&>>file` redirects both stdout and stderr
+            "&>" => (1, RedirectMode::Write, true),
+            "&>>" => (1, RedirectMode::Append, true),
+            _ => (0, RedirectMode::Read, false),
+        };
+        let target = match self.peek_kind() {
+            Some(TokenKind::Word(_) | TokenKind::Assignment(_)) => match self.next() {
+                Some(Token { kind: TokenKind::Word(word) | TokenKind::Assignment(word), .. }) => word,
+                _ => unreachable!(),
+            },
+            _ => return Err(self.invalid("no file path provided".to_string(), self.last_end - op.len()..self.last_end)),
+        };
+        push_redirect(redirects, fd.unwrap_or(default_fd), mode, target, with_stderr);
+        Ok(())
+    }
+}
+
+/// Quotes the string so that it is parsed back as a single word with the same content
+pub fn quote(s: &str) -> String {
+    let is_plain = !s.is_empty()
+        && s.chars().all(|ch| ch.is_ascii_alphanumeric() || "_-+=/.,:@%".contains(ch));
+    if is_plain {
+        return s.to_string();
+    }
+    // A single quote can't appear inside single quotes, so it is put inside double quotes.
+    format!("'{}'", s.replace('\'', "'\"'\"'"))
+}
+
+/// Adds the redirection, where `>&-` closes the file descriptor,
+/// and `with_stderr` additionally redirects stderr to the same place like `&>`.
+fn push_redirect(redirects: &mut Vec<Redirect>, fd: i32, mode: RedirectMode, target: Word, with_stderr: bool) {
+    let is_dash = matches!(target.parts.as_slice(), [WordPart::Literal { text, quoted: false }] if text == "-");
+    match mode {
+        RedirectMode::Dup if is_dash => redirects.push(Redirect { fd, mode: RedirectMode::Close, target: Word::new() }),
+        _ => redirects.push(Redirect { fd, mode, target }),
+    }
+    if with_stderr {
+        let mut target = Word::new();
+        target.push('1', false);
+        redirects.push(Redirect { fd: 2, mode: RedirectMode::Dup, target });
+    }
+}
+
+/// A function can be named like a command, e.g., `git-sync`, unless it looks like an assignment or a path.
+fn is_function_name(name: &str) -> bool {
+    !name.is_empty()
+        && !RESERVED_WORDS.contains(&name)
+        && name.chars().all(|ch| ch.is_ascii_alphanumeric() || "_-.:".contains(ch))
+}
@@ -0,0 +1,132 @@
+use nix::unistd::{geteuid, gethostname, User};
+use std::env;
+use std::ffi::CString;
+
+use crate::shell::Shell;
+
+/// Marks the beginning of the characters taking no space on the terminal, e.g., a color sequence, as `\[` does
+pub const INVISIBLE_START: char = '\x01';
+/// Marks the end of the characters taking no space on the terminal, as `\]` does
+pub const INVISIBLE_END: char = '\x02';
+
+/// Returns the prompt given by the variable like `PS1` with the escapes replaced, or `default` if it is unset
+///
+/// The characters between `\[` and `\]` are enclosed in `INVISIBLE_START` and `INVISIBLE_END`.
+pub fn expand(shell: &Shell, name: &str, default: &str) -> String {
+    let ps = shell.vars.get(name).unwrap_or(default);
+    let mut prompt = String::new();
+    let mut chars = ps.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            prompt.push(ch);
+            continue;
+        }
+        let Some(escape) = chars.next() else {
+            prompt.push('\\');
+            break;
+        };
+        match escape {
+            'a' => prompt.push('\x07'),
+            'e' => prompt.push('\x1b'),
+            'n' => prompt.push('\n'),
+            'r' => prompt.push('\r'),
+            '\\' => prompt.push('\\'),
+            '[' => prompt.push(INVISIBLE_START),
+            ']' => prompt.push(INVISIBLE_END),
+            '$' => prompt.push(if geteuid().is_root() { '#' } else { '$' }),
+            '?' => prompt.push_str(&shell.last_status.to_string()),
+            'j' => prompt.push_str(&shell.jobs.iter().count().to_string()),
+            's' => prompt.push_str(shell.name.rsplit('/').next().unwrap_or_default()),
+            // the version without the patch level, or the full one
+            'v' => prompt.push_str(concat!(env!("CARGO_PKG_VERSION_MAJOR"), ".", env!("CARGO_PKG_VERSION_MINOR"))),
+            'V' => prompt.push_str(env!("CARGO_PKG_VERSION")),
+            'u' => prompt.push_str(&user_name(shell)),
+            'h' => prompt.push_str(host_name().split('.').next().unwrap_or_default()),
+            'H' => prompt.push_str(&host_name()),
+            'w' => prompt.push_str(&working_dir(shell)),
+            'W' => {
+                let dir = working_dir(shell);
+                match dir.rsplit_once('/') {
+                    Some((_, name)) if !name.is_empty() => prompt.push_str(name),
+                    _ => prompt.push_str(&dir),
+                }
+            },
+            'd' => prompt.push_str(&strftime("%a %b %d")),
+            't' => prompt.push_str(&strftime("%H:%M:%S")),
+            'T' => prompt.push_str(&strftime("%I:%M:%S")),
+            '@' => prompt.push_str(&strftime("%I:%M %p")),
+            'A' => prompt.push_str(&strftime("%H:%M")),
+            // `\D{FORMAT}` formats the time with strftime(3)
+            'D' if chars.peek() == Some(&'{') => {
+                chars.next();
+                let format: String = chars.by_ref().take_while(|&ch| ch != '}').collect();
+                prompt.push_str(&strftime(if format.is_empty() { "%X" } else { &format }));
+            },
+            // up to three octal digits
+            '0'..='7' => {
+                let mut code = escape.to_digit(8).unwrap_or_default();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|ch| ch.to_digit(8)) {
+                        Some(digit) => {
+                            code = code * 8 + digit;
+                            chars.next();
+                        },
+                        None => break,
+                    }
+                }
+                prompt.extend(char::from_u32(code & 0xff));
+            },
+            _ => {
+                prompt.push('\\');
+                prompt.push(escape);
+            },
+        }
+    }
+    prompt
+}
+
+/// Returns the prompt as it is printed, i.e., without `INVISIBLE_START` and `INVISIBLE_END`
+pub fn printable(prompt: &str) -> String {
+    prompt.replace([INVISIBLE_START, INVISIBLE_END], "")
+}
+
+fn user_name(shell: &Shell) -> String {
+    match User::from_uid(geteuid()) {
+        Ok(Some(user)) => user.name,
+        _ => shell.vars.get("USER").unwrap_or_default().to_string(),
+    }
+}
+
+fn host_name() -> String {
+    gethostname().ok().and_then(|name| name.into_string().ok()).unwrap_or_default()
+}
+
+/// Returns the working directory of the shell, where the home directory is abbreviated to `~`
+///
+/// It doesn't follow `$PWD`, which may be assigned anything.
+fn working_dir(shell: &Shell) -> String {
+    let dir = shell.cwd.display().to_string();
+    match shell.vars.get("HOME").filter(|home| !home.is_empty() && *home != "/") {
+        Some(home) if dir == home => "~".to_string(),
+        Some(home) if dir.strip_prefix(home).is_some_and(|rest| rest.starts_with('/')) => {
+            format!("~{}", &dir[home.len()..])
+        },
+        _ => dir,
+    }
+}
+
+/// Formats the current local time with strftime(3)
+fn strftime(format: &str) -> String {
+    let Ok(format) = CString::new(format) else {
+        return String::new();
+    };
+    let mut buf = [0u8; 256];
+    let len = unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+        libc::strftime(buf.as_mut_ptr().cast(), buf.len(), format.as_ptr(), &tm)
+    };
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
@@ -0,0 +1,143 @@
+use std::ffi::{c_char, c_ulong, c_void, CString};
+use std::mem::{size_of, MaybeUninit};
+
+/// Characters that have to be escaped with `\` to be matched literally in a regular expression
+fn is_special(ch: char) -> bool {
+    matches!(ch, '\\' | '.' | '[' | ']' | '(' | ')' | '*' | '+' | '?' | '{' | '}' | '|' | '^' | '$')
+}
+
+/// Escapes the string, so that it matches itself, e.g., for the quoted parts of the right side of `=~`
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::new();
+    for ch in s.chars() {
+        if is_special(ch) {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+/// The layout of `regex_t` of glibc, whose `re_nsub` is private in the libc crate
+#[repr(C)]
+struct RegexLayout {
+    buffer: *mut c_void,
+    allocated: usize,
+    used: usize,
+    syntax: c_ulong,
+    fastmap: *mut c_char,
+    translate: *mut c_char,
+    /// the number of the parenthesized subexpressions
+    re_nsub: usize,
+    bitfield: u8,
+}
+
+const _: () = assert!(size_of::<RegexLayout>() == size_of::<libc::regex_t>());
+
+/// A POSIX extended regular expression compiled by regcomp(3) like Bash does
+pub struct Regex {
+    // boxed so that it stays where regcomp(3) initialized it
+    raw: Box<libc::regex_t>,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let c_pattern = CString::new(pattern).map_err(|_| "the pattern contains a NUL character".to_string())?;
+        let mut raw: Box<MaybeUninit<libc::regex_t>> = Box::new(MaybeUninit::uninit());
+        let code = unsafe { libc::regcomp(raw.as_mut_ptr(), c_pattern.as_ptr(), libc::REG_EXTENDED) };
+        if code != 0 {
+            return Err(error_message(code, raw.as_ptr()));
+        }
+        // SAFETY: regcomp(3) succeeded, so the whole struct is initialized.
+        let raw = unsafe { Box::from_raw(Box::into_raw(raw).cast::<libc::regex_t>()) };
+        Ok(Regex { raw })
+    }
+
+    /// Returns the number of the groups counted by regcomp(3)
+    fn groups(&self) -> usize {
+        let raw: *const libc::regex_t = &*self.raw;
+        unsafe { (*raw.cast::<RegexLayout>()).re_nsub }
+    }
+
+    /// Returns the leftmost longest match followed by the text matched by each group,
+    /// which is empty if the group doesn't participate in the match
+    pub fn captures(&self, s: &str) -> Option<Vec<String>> {
+        // The text can't be matched beyond a NUL character, which a variable never contains anyway.
+        let c_text = CString::new(s.split('\0').next().unwrap_or_default()).ok()?;
+        let mut matches = vec![libc::regmatch_t { rm_so: -1, rm_eo: -1 }; self.groups() + 1];
+        let code = unsafe { libc::regexec(&*self.raw, c_text.as_ptr(), matches.len(), matches.as_mut_ptr(), 0) };
+        if code != 0 {
+            return None;
+        }
+        let bytes = c_text.as_bytes();
+        let texts = matches.iter()
+            .map(|m| match (usize::try_from(m.rm_so), usize::try_from(m.rm_eo)) {
+                (Ok(start), Ok(end)) => String::from_utf8_lossy(&bytes[start..end]).into_owned(),
+                _ => String::new(),
+            })
+            .collect();
+        Some(texts)
+    }
+}
+
+impl Drop for Regex {
+    fn drop(&mut self) {
+        unsafe { libc::regfree(&mut *self.raw) };
+    }
+}
+
+/// Returns the message of the error code given by regcomp(3)
+fn error_message(code: libc::c_int, raw: *const libc::regex_t) -> String {
+    let mut buf = [0u8; 256];
+    let len = unsafe { libc::regerror(code, raw, buf.as_mut_ptr().cast(), buf.len()) };
+    let len = len.min(buf.len()).saturating_sub(1);
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn captures(pattern: &str, s: &str) -> Option<Vec<String>> {
+        Regex::new(pattern).unwrap_or_else(|err| panic!("{pattern}: {err}")).captures(s)
+    }
+
+    #[test]
+    fn escaped_text_matches_itself() {
+        let s = "a.b*(c)|[d]{1}^$\\";
+        assert_eq!(captures(&format!("^{}$", escape(s)), s), Some(vec![s.to_string()]));
+        assert_eq!(captures(&escape("a.c"), "abc"), None);
+    }
+
+    #[test]
+    fn match_is_leftmost_longest() {
+        assert_eq!(captures("a+", "baaab"), Some(vec!["aaa".to_string()]));
+        assert_eq!(captures("x*", "abc"), Some(vec![String::new()]));
+        assert_eq!(captures("a|ab|abc", "xabcd"), Some(vec!["abc".to_string()]));
+    }
+
+    #[test]
+    fn groups_are_captured() {
+        let groups = captures("([a-z]+)-([0-9]+)(x)?", "id: abc-123!");
+        assert_eq!(groups, Some(vec!["abc-123", "abc", "123", ""].into_iter().map(String::from).collect()));
+    }
+
+    #[test]
+    fn brackets_and_escapes_are_not_groups() {
+        assert_eq!(Regex::new(r"\(a\)[(][]()][^](][[:alpha:](](b)").unwrap().groups(), 1);
+        assert_eq!(captures(r"[(]([[:digit:]]+)[)]", "f(42)"), Some(vec!["(42)".to_string(), "42".to_string()]));
+    }
+
+    #[test]
+    fn nested_repetition_takes_no_exponential_time() {
+        let s = format!("{}b", "a".repeat(40));
+        assert_eq!(captures("^(a*)*c$", &s), None);
+        assert_eq!(captures("^(a*)*b$", &s).map(|groups| groups.len()), Some(2));
+    }
+
+    #[test]
+    fn invalid_pattern_is_an_error() {
+        assert!(Regex::new("a(b").is_err());
+        assert!(Regex::new("[a").is_err());
+    }
+}
@@ -0,0 +1,154 @@
+use nix::errno::Errno;
+use std::fs;
+use std::path::Path;
+
+use crate::executor::exec_list;
+use crate::input::Input;
+use crate::parser::parse;
+use crate::shell::{Flow, Shell};
+use crate::types::error::{ShellError, ParseError, ParseErrorKind};
+
+/// The maximum number of the files being executed by `source` at a time, which keeps a file sourcing itself
+/// from overflowing the stack
+const MAX_SOURCE_DEPTH: usize = 1000;
+
+/// Executes the file in the shell, where the positional parameters are replaced with `args` if given until it ends,
+/// and returns the exit status of the last command
+pub fn source(shell: &mut Shell, path: &Path, args: Option<&[String]>) -> Result<i32, ShellError> {
+    if shell.source_depth >= MAX_SOURCE_DEPTH {
+        return Err(ShellError::Builtin(format!("maximum source nesting level exceeded ({MAX_SOURCE_DEPTH})")));
+    }
+    let text = fs::read(shell.path(path)).map_err(|err| ShellError::Builtin(format!("{}: {err}", path.display())))?;
+    let mut input = Input::script(String::from_utf8_lossy(&text).into_owned(), path.display().to_string());
+
+    let positional = args.map(|args| std::mem::replace(&mut shell.positional, args.to_vec()));
+    shell.source_depth += 1;
+    // an empty file succeeds
+    shell.last_status = 0;
+    run(shell, &mut input);
+    shell.source_depth -= 1;
+    if let Some(positional) = positional {
+        shell.positional = positional;
+    }
+    Ok(shell.last_status)
+}
+
+/// Appends continuation lines to `line` until the one equal to `delim` regardless of leading tabs,
+/// or just one line if `delim` is `None`
+///
+/// Returns `Ok(false)` on EOF.
+fn read_until(shell: &mut Shell, input: &mut Input, line: &mut String, delim: Option<&str>) -> Result<bool, Errno> {
+    loop {
+        let Some(next_line) = input.read_line("PS2", shell)? else {
+            return Ok(false);
+        };
+        line.push_str(&next_line);
+        if delim.is_none_or(|delim| next_line.trim_start_matches('\t').trim_end_matches('\n').trim_end_matches('\r') == delim) {
+            return Ok(true);
+        }
+    }
+}
+
+/// Reads and executes the commands until EOF, `exit`, or `return` in a sourced file
+pub fn run(shell: &mut Shell, input: &mut Input) {
+    // the number of the line the next command begins at
+    let mut line_number = 1;
+    'repl: loop {
+        // report the jobs done or stopped since the last prompt
+        shell.notify_jobs(shell.job_control);
+
+        if input.is_interactive() {
+            run_prompt_command(shell);
+        }
+        // e.g., `exit` in a startup file
+        if let Some(Flow::Exit(_)) = shell.flow {
+            break;
+        }
+        let mut line = match input.read_line("PS1", shell) {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(Errno::EINTR) => {
+                // Ctrl-C cancels the current line
+                println!();
+                shell.last_status = 130;
+                continue;
+            },
+            Err(err) => {
+                eprintln!("shell: failed to read line: {}", err.desc());
+                break;
+            },
+        };
+
+        // keep reading lines until the command is complete, e.g., for the body of a here-document or a loop
+        let res = loop {
+            let (msg, delim) = match parse(&line) {
+                Err(ShellError::IncompleteInput { msg, delim }) => (msg, delim),
+                res => break res,
+            };
+            match read_until(shell, input, &mut line, delim.as_deref()) {
+                Ok(true) => {},
+                Err(Errno::EINTR) => {
+                    // Ctrl-C cancels the whole command
+                    println!();
+                    shell.last_status = 130;
+                    continue 'repl;
+                },
+                // The error is reported at the end of the input.
+                _ => {
+                    let end = line.trim_end_matches('\n').len();
+                    break Err(ParseError::new(ParseErrorKind::UnexpectedEnd(msg), &line, end..end).into());
+                },
+            }
+        };
+
+        input.add_history(&line);
+        let first_line = line_number;
+        line_number += line.matches('\n').count();
+        match res {
+            Ok(list) => {
+                exec_list(shell, &list);
+                match shell.flow {
+                    Some(Flow::Return(_)) => {
+                        shell.flow = None;
+                        break;
+                    },
+                    // It's left for the caller, e.g., `source` leaves the file and the shell exits too.
+                    Some(Flow::Exit(_)) => break,
+                    _ => shell.flow = None,
+                }
+            },
+            Err(err) => {
+                let location = match &err {
+                    ShellError::Parse(parse_err) => input.describe_line(first_line + parse_err.line - 1),
+                    _ => None,
+                };
+                match location {
+                    Some(location) => shell.print_error(format_args!("shell: {location}: {err}")),
+                    None => shell.print_error(format_args!("shell: {err}")),
+                }
+                shell.last_status = err.status();
+                // A script can't go on without the commands it fails to parse.
+                if !input.is_interactive() {
+                    break;
+                }
+            },
+        }
+    }
+}
+
+/// Runs `$PROMPT_COMMAND` if set, which leaves `$?` as it was for the prompt
+fn run_prompt_command(shell: &mut Shell) {
+    let Some(command) = shell.vars.get("PROMPT_COMMAND").map(|command| format!("{command}\n")) else {
+        return;
+    };
+    let last_status = shell.last_status;
+    match parse(&command) {
+        Ok(list) => exec_list(shell, &list),
+        Err(err) => shell.print_error(format_args!("shell: PROMPT_COMMAND: {err}")),
+    }
+    if let Some(Flow::Exit(_)) = shell.flow {
+        return;
+    }
+    shell.flow = None;
+    shell.last_status = last_status;
+}
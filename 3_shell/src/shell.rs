@@ -0,0 +1,308 @@
+use nix::errno::Errno;
+use nix::unistd::{access, getpid, setpgid, AccessFlags, Pid};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::{env, fmt, marker::PhantomData, rc::Rc};
+
+use crate::complete::CompletionSpec;
+use crate::executor::jobs::{set_foreground, JobTable};
+use crate::signals;
+use crate::types::command::Command;
+use crate::types::error::ShellError;
+use crate::variables::Variables;
+
+/// Options toggled by `shopt`
+#[derive(Default)]
+pub struct Shopts {
+    /// Whether a glob pattern matching nothing is an error
+    pub failglob: bool,
+    /// Whether a glob pattern matching nothing expands to nothing rather than itself
+    pub nullglob: bool,
+}
+
+impl Shopts {
+    pub const NAMES: [&str; 2] = ["failglob", "nullglob"];
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "failglob" => Some(&mut self.failglob),
+            "nullglob" => Some(&mut self.nullglob),
+            _ => None,
+        }
+    }
+}
+
+/// Options toggled by `set`
+#[derive(Default)]
+pub struct SetOptions {
+    /// `-e`, exit if a pipeline fails
+    pub errexit: bool,
+    /// `-u`, treat expanding an unset parameter as an error
+    pub nounset: bool,
+    /// `-x`, print each command to stderr after it is expanded
+    pub xtrace: bool,
+}
+
+impl SetOptions {
+    pub const NAMES: [&str; 3] = ["errexit", "nounset", "xtrace"];
+
+    /// Returns the name of the option for the flag, e.g., `errexit` for `-e`
+    pub fn long_name(flag: char) -> Option<&'static str> {
+        match flag {
+            'e' => Some("errexit"),
+            'u' => Some("nounset"),
+            'x' => Some("xtrace"),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "errexit" => Some(&mut self.errexit),
+            "nounset" => Some(&mut self.nounset),
+            "xtrace" => Some(&mut self.xtrace),
+            _ => None,
+        }
+    }
+}
+
+/// What breaks the normal flow of the commands
+#[derive(Clone, Copy)]
+pub enum Flow {
+    /// `break N`
+    Break(usize),
+    /// `continue N`
+    Continue(usize),
+    /// `return N`
+    Return(i32),
+    /// a foreground job interrupted by Ctrl-C or Ctrl-Z, which stops the rest of the command line
+    Interrupt,
+    /// `exit N`, or a failure ending a non-interactive shell, which stops all the commands being executed
+    Exit(i32),
+}
+
+/// A builtin defined by the program embedding the shell, which is called with the arguments including its name
+pub type BuiltinFn = Rc<dyn Fn(&mut Shell, &[String]) -> Result<i32, ShellError>>;
+
+/// File descriptors below this are left for the redirections of the users.
+pub const MIN_SHELL_FD: RawFd = 10;
+
+/// Writes to a file descriptor of the shell, e.g., for the output of a builtin
+pub struct FdWriter<'a> {
+    /// the file descriptor of the process it refers to, or `None` if it is closed
+    fd: Option<RawFd>,
+    _shell: PhantomData<&'a Shell>,
+}
+
+impl Write for FdWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let fd = self.fd.ok_or_else(|| io::Error::from(Errno::EBADF))?;
+        let len = unsafe { libc::write(fd, buf.as_ptr().cast(), buf.len()) };
+        Ok(Errno::result(len)? as usize)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    // formatted at once, so that a line is written by a single system call
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> io::Result<()> {
+        self.write_all(fmt::format(args).as_bytes())
+    }
+}
+
+/// States kept across command lines
+pub struct Shell {
+    pub last_status: i32,
+    /// whether the commands are read from a terminal
+    pub interactive: bool,
+    /// Whether to put each job into its own process group and hand the terminal over to the foreground one.
+    /// It is only enabled for an interactive shell, and is disabled in subshells.
+    pub job_control: bool,
+    pub pgid: Pid,
+    pub jobs: JobTable,
+    pub vars: Variables,
+    /// `$$`, which is not changed in subshells
+    pub pid: Pid,
+    /// `$!`, the process ID of the last background job
+    pub last_bg_pid: Option<Pid>,
+    pub shopts: Shopts,
+    pub opts: SetOptions,
+    /// `$0`
+    pub name: String,
+    /// `$1`, `$2`, ...
+    pub positional: Vec<String>,
+    /// set while the commands are skipped up to where the flow resumes
+    pub flow: Option<Flow>,
+    /// the number of the loops being executed in the current function
+    pub loop_depth: usize,
+    /// the bodies of the functions by name
+    pub functions: HashMap<String, Rc<Command>>,
+    /// the number of the functions being called
+    pub func_depth: usize,
+    /// the number of the conditions of `if`, `while` or `until` being executed
+    pub cond_depth: usize,
+    /// the number of the files being executed by `source`
+    pub source_depth: usize,
+    /// how the arguments are completed by command name, which is set by `complete`
+    pub completions: HashMap<String, CompletionSpec>,
+    /// the values of the aliases by name
+    pub aliases: HashMap<String, String>,
+    /// the aliases whose values are being executed, which are not replaced again
+    pub aliases_in_use: Vec<String>,
+    /// the builtins registered by `Shell::register_builtin` by name
+    pub builtins: HashMap<String, BuiltinFn>,
+    /// The files the commands read from and write to in place of the file descriptors of the process,
+    /// where `None` means it's closed, e.g., for the redirections of a builtin or the handles given by `set_handle`.
+    /// Only a forked child replaces the ones of the process with them.
+    pub fds: BTreeMap<RawFd, Option<File>>,
+    /// the working directory, which only a forked child changes the one of the process to
+    pub cwd: PathBuf,
+}
+
+impl Shell {
+    /// Only an interactive shell does job control.
+    pub fn new(interactive: bool) -> Self {
+        let job_control = interactive;
+        let pgid = getpid();
+        let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
+
+        // An inherited `$PWD` is kept only if it's an absolute path to the working directory like in Bash,
+        // e.g., through a symbolic link.
+        let mut vars = Variables::from_env();
+        let names_cwd = vars.get("PWD")
+            .filter(|pwd| pwd.starts_with('/'))
+            .and_then(|pwd| fs::metadata(pwd).ok())
+            .zip(fs::metadata(&cwd).ok())
+            .is_some_and(|(pwd, cwd)| pwd.dev() == cwd.dev() && pwd.ino() == cwd.ino());
+        if !names_cwd {
+            vars.set("PWD", cwd.display().to_string());
+        }
+        vars.export("PWD");
+
+        if job_control {
+            signals::install();
+            // lead a process group and take the terminal
+            let _ = setpgid(pgid, pgid);
+            set_foreground(pgid);
+        }
+
+        Self {
+            last_status: 0,
+            interactive,
+            job_control,
+            pgid,
+            jobs: JobTable::new(),
+            vars,
+            pid: pgid,
+            last_bg_pid: None,
+            shopts: Shopts::default(),
+            opts: SetOptions::default(),
+            name: String::new(),
+            positional: vec![],
+            flow: None,
+            loop_depth: 0,
+            functions: HashMap::new(),
+            func_depth: 0,
+            cond_depth: 0,
+            source_depth: 0,
+            completions: HashMap::new(),
+            aliases: HashMap::new(),
+            aliases_in_use: vec![],
+            builtins: HashMap::new(),
+            fds: BTreeMap::new(),
+            cwd,
+        }
+    }
+
+    /// Returns the path relative to the working directory of the shell as is seen from the process
+    pub fn path(&self, path: impl AsRef<Path>) -> PathBuf {
+        // An empty path stays empty, which names no file.
+        match path.as_ref().as_os_str().is_empty() {
+            true => PathBuf::new(),
+            false => self.cwd.join(path),
+        }
+    }
+
+    /// Changes the working directory of the shell, and updates `$PWD` and `$OLDPWD`
+    pub fn set_cwd(&mut self, path: &Path) -> io::Result<()> {
+        // the symbolic links resolved like the one of the process
+        let path = self.path(path).canonicalize()?;
+        if !fs::metadata(&path)?.is_dir() {
+            return Err(Errno::ENOTDIR.into());
+        }
+        access(&path, AccessFlags::X_OK)?;
+
+        if let Some(old_pwd) = self.vars.get("PWD").map(str::to_string) {
+            self.vars.set("OLDPWD", old_pwd);
+        }
+        self.vars.set("PWD", path.display().to_string());
+        self.cwd = path;
+        Ok(())
+    }
+
+    /// Returns the file descriptor of the process that the one of the shell refers to, or `None` if it's closed
+    pub fn raw_fd(&self, fd: RawFd) -> Option<RawFd> {
+        match self.fds.get(&fd) {
+            Some(file) => file.as_ref().map(File::as_raw_fd),
+            None => Some(fd),
+        }
+    }
+
+    /// Makes the file descriptor of the shell refer to the file, or closed if `None`,
+    /// and returns what it referred to before, where `None` means the one of the process
+    pub fn replace_fd(&mut self, fd: RawFd, file: Option<File>) -> io::Result<Option<Option<File>>> {
+        // It's moved out of the way of the ones replaced by a forked child, and isn't inherited by the programs.
+        let file = match file {
+            Some(file) => {
+                let dup = Errno::result(unsafe { libc::fcntl(file.as_raw_fd(), libc::F_DUPFD_CLOEXEC, MIN_SHELL_FD) })?;
+                Some(unsafe { File::from_raw_fd(dup) })
+            },
+            None => None,
+        };
+        Ok(self.fds.insert(fd, file))
+    }
+
+    /// Makes the file descriptor refer to what `replace_fd` returned
+    pub fn restore_fd(&mut self, fd: RawFd, prev: Option<Option<File>>) {
+        match prev {
+            Some(file) => self.fds.insert(fd, file),
+            None => self.fds.remove(&fd),
+        };
+    }
+
+    /// Makes the commands read from or write to the handle by the file descriptor, e.g., 1 for stdout,
+    /// in place of the one of the process
+    pub fn set_handle(&mut self, fd: RawFd, handle: impl Into<OwnedFd>) -> io::Result<()> {
+        self.replace_fd(fd, Some(File::from(handle.into()))).map(|_| ())
+    }
+
+    pub fn writer(&self, fd: RawFd) -> FdWriter<'_> {
+        FdWriter { fd: self.raw_fd(fd), _shell: PhantomData }
+    }
+
+    pub fn stdout(&self) -> FdWriter<'_> {
+        self.writer(1)
+    }
+
+    pub fn stderr(&self) -> FdWriter<'_> {
+        self.writer(2)
+    }
+
+    /// Updates the states of the jobs and removes the done ones,
+    /// where the ones whose states have changed are reported to stderr if `verbose`
+    pub fn notify_jobs(&mut self, verbose: bool) {
+        for report in self.jobs.notify(verbose) {
+            self.print_error(report);
+        }
+    }
+
+    /// Writes the line to stderr, where it's lost if it can't be written
+    pub fn print_error(&self, msg: impl fmt::Display) {
+        let _ = writeln!(self.stderr(), "{msg}");
+    }
+}
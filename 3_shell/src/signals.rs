@@ -0,0 +1,37 @@
+use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// The signals that an interactive shell handles by itself
+const HANDLED_SIGNALS: [Signal; 4] = [Signal::SIGINT, Signal::SIGQUIT, Signal::SIGTSTP, Signal::SIGTTOU];
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Does nothing but interrupts a blocking `read`, so that the current input line gets cancelled
+extern "C" fn on_sigint(_: libc::c_int) {}
+
+/// Keeps an interactive shell from being killed or stopped by the keyboard.
+///
+/// `SIGINT` is caught rather than ignored, and `SA_RESTART` is not set,
+/// so a blocking `read` at the prompt fails with `EINTR` on Ctrl-C.
+pub fn install() {
+    for sig in HANDLED_SIGNALS {
+        let handler = match sig {
+            Signal::SIGINT => SigHandler::Handler(on_sigint),
+            _ => SigHandler::SigIgn,
+        };
+        let action = SigAction::new(handler, SaFlags::empty(), SigSet::empty());
+        let _ = unsafe { sigaction(sig, &action) };
+    }
+    INSTALLED.store(true, Ordering::Relaxed);
+}
+
+/// Restores the default dispositions in a child process, if they have been changed by `install`
+pub fn reset() {
+    if !INSTALLED.swap(false, Ordering::Relaxed) {
+        return;
+    }
+    let action = SigAction::new(SigHandler::SigDfl, SaFlags::empty(), SigSet::empty());
+    for sig in HANDLED_SIGNALS {
+        let _ = unsafe { sigaction(sig, &action) };
+    }
+}
@@ -1,5 +1,143 @@
-pub struct Command {
-    pub args: Vec<String>,
-    pub files_in: Vec<String>,
-    pub files_out: Vec<String>,
+use std::rc::Rc;
+
+use crate::types::word::Word;
+
+#[derive(Clone, Copy)]
+pub enum RedirectMode {
+    /// `<`
+    Read,
+    /// `>`
+    Write,
+    /// `>>`
+    Append,
+    /// `<>`
+    ReadWrite,
+    /// `>&M` or `<&M`, where the target is the file descriptor to duplicate
+    Dup,
+    /// `>&-` or `<&-`
+    Close,
+    /// `<<DELIM` or `<<-DELIM`, where the target is the body following the line.
+    /// The body is expanded unless any part of `DELIM` is quoted.
+    HereDoc,
+    /// `<<<word`, where the target is fed with a trailing newline
+    HereString,
+}
+
+/// `[N]<op>target`, where `N` defaults to 0 for `<` and 1 for `>`
+#[derive(Clone)]
+pub struct Redirect {
+    pub fd: i32,
+    pub mode: RedirectMode,
+    pub target: Word,
+}
+
+#[derive(Clone, Default)]
+pub struct SimpleCommand {
+    /// leading `NAME=value`s, which only affect the command if there are arguments, or the shell otherwise
+    pub assigns: Vec<(String, Word)>,
+    pub args: Vec<Word>,
+    /// applied from left to right, where the files redirected to the same file descriptor
+    /// in the same direction are merged or spread, e.g., `cat < a < b` or `echo > a > b`
+    pub redirects: Vec<Redirect>,
+}
+
+#[derive(Clone)]
+pub enum CompoundCommand {
+    /// `{ list; }`
+    BraceGroup(CommandList),
+    /// `( list )`, which is executed in a subshell
+    Subshell(CommandList),
+    /// `if list; then list; [elif list; then list;]... [else list;] fi`
+    If { branches: Vec<(CommandList, CommandList)>, else_body: Option<CommandList> },
+    /// `while list; do list; done`, or `until list; do list; done` if `until`
+    While { cond: CommandList, body: CommandList, until: bool },
+    /// `for NAME [in WORD...]; do list; done`, which iterates over `"$@"` if `words` is `None`
+    For { name: String, words: Option<Vec<Word>>, body: CommandList },
+    /// `case WORD in [(]PATTERN[|PATTERN]...) list;; ... esac`
+    Case { word: Word, items: Vec<CaseItem> },
+    /// `((expr))`, which succeeds if the value is non-zero
+    Arith(Word),
+    /// `[[ expr ]]`, whose words are neither split nor globbed
+    Cond(CondExpr),
+}
+
+/// An expression of `[[ ]]`
+#[derive(Clone)]
+pub enum CondExpr {
+    /// `! expr`
+    Not(Box<CondExpr>),
+    /// `expr && expr`
+    And(Box<CondExpr>, Box<CondExpr>),
+    /// `expr || expr`
+    Or(Box<CondExpr>, Box<CondExpr>),
+    /// `-op word`, e.g., `-f path`
+    Unary { op: String, arg: Word },
+    /// `word op word`, where the right side is a pattern for `==` and `!=`,
+    /// or a regular expression for `=~`
+    Binary { op: String, lhs: Word, rhs: Word },
+    /// a word alone, which is true if it is non-empty
+    Word(Word),
+}
+
+/// What follows the list of a case item
+#[derive(Clone, Copy)]
+pub enum CaseTerminator {
+    /// `;;`, which ends the `case`
+    Break,
+    /// `;&`, which executes the list of the next item without testing it
+    FallThrough,
+    /// `;;&`, which goes on testing the next items
+    Continue,
+}
+
+#[derive(Clone)]
+pub struct CaseItem {
+    pub patterns: Vec<Word>,
+    pub body: CommandList,
+    pub terminator: CaseTerminator,
+}
+
+#[derive(Clone)]
+pub enum Command {
+    Simple(SimpleCommand),
+    Compound { body: CompoundCommand, redirects: Vec<Redirect> },
+    /// `NAME() compound-command` or `function NAME compound-command`,
+    /// whose body is shared with the function table once defined
+    FunctionDef { name: String, body: Rc<Command> },
+}
+
+/// Commands connected by `|`
+#[derive(Clone)]
+pub struct Pipeline {
+    pub cmds: Vec<Command>,
+    /// whether it begins with `!`, which inverts the exit status
+    pub negated: bool,
+    /// the source text, which is shown for a job
+    pub text: String,
+}
+
+#[derive(Clone)]
+pub enum AndOrOp {
+    /// `&&`
+    And,
+    /// `||`
+    Or,
+}
+
+/// Pipelines connected by `&&` and `||`, which are evaluated from left to right
+/// with the same precedence
+#[derive(Clone)]
+pub struct AndOrList {
+    pub first: Pipeline,
+    pub rest: Vec<(AndOrOp, Pipeline)>,
+    /// whether it is terminated by `&` and thus executed asynchronously in a subshell
+    pub background: bool,
+    /// the source text, which is shown for a background job
+    pub text: String,
+}
+
+/// `AndOrList`s separated by `;`, `&` or newlines
+#[derive(Clone)]
+pub struct CommandList {
+    pub items: Vec<AndOrList>,
 }
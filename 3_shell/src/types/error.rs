@@ -1,33 +1,169 @@
+use nix::errno::Errno;
+use std::error::Error;
 use std::fmt;
+use std::io;
+use std::ops::Range;
 
-pub enum GenericError {
-    IOError(std::io::Error),
-    OtherError(String),
+/// Why a command fails, which decides its exit status
+#[derive(Debug)]
+pub enum ShellError {
+    /// a syntax error
+    Parse(ParseError),
+    /// The input ends in the middle of a command, so more lines should be read if possible.
+    /// `delim` is the line that may complete the input if known, e.g., the delimiter of a here-document.
+    IncompleteInput { msg: String, delim: Option<String> },
+    /// an expansion fails, e.g., for an unbound variable or a division by zero
+    Expansion(String),
+    /// the target of a redirection can't be opened or used, e.g., a bad file descriptor
+    Redirect { target: String, source: io::Error },
+    /// the command is neither a function, a builtin nor an executable in `$PATH`
+    CommandNotFound,
+    /// the program can't be executed
+    Exec(Errno),
+    /// a builtin or a conditional expression is used wrongly, e.g., with an invalid option
+    Usage(String),
+    /// a builtin can't do what is asked, e.g., `cd` without `$HOME`
+    Builtin(String),
+    /// a job can't be found or controlled
+    Job(String),
+    /// the shell itself fails, e.g., to fork or to create a pipe, or nests functions too deeply
+    Internal { msg: String, source: Option<io::Error> },
+    Io(io::Error),
 }
 
-impl fmt::Display for GenericError {
+impl ShellError {
+    /// Returns the error of the system call, where `msg` tells what failed, e.g., `fork: failed to fork`
+    pub fn internal(msg: &str, errno: Errno) -> Self {
+        ShellError::Internal { msg: msg.to_string(), source: Some(errno.into()) }
+    }
+
+    /// Returns the exit status of the command failing with the error
+    pub fn status(&self) -> i32 {
+        match self {
+            ShellError::Parse(_) | ShellError::IncompleteInput { .. } | ShellError::Usage(_) => 2,
+            ShellError::CommandNotFound | ShellError::Exec(Errno::ENOENT) => 127,
+            ShellError::Exec(_) => 126,
+            _ => 1,
+        }
+    }
+}
+
+impl fmt::Display for ShellError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            GenericError::IOError(err) => write!(f, "{err}"),
-            GenericError::OtherError(s) => write!(f, "{s}"),
+            ShellError::Parse(err) => write!(f, "{err}"),
+            ShellError::IncompleteInput { msg, .. } => write!(f, "{msg}"),
+            ShellError::Redirect { target, source } => write!(f, "{target}: {source}"),
+            ShellError::CommandNotFound => write!(f, "command not found!"),
+            ShellError::Exec(Errno::ENOENT) => write!(f, "No such file or directory"),
+            ShellError::Exec(Errno::EACCES) => write!(f, "permission denied"),
+            ShellError::Exec(errno) => write!(f, "{}", errno.desc()),
+            ShellError::Expansion(msg) | ShellError::Usage(msg) | ShellError::Builtin(msg) | ShellError::Job(msg)
+            | ShellError::Internal { msg, .. } => write!(f, "{msg}"),
+            ShellError::Io(err) => write!(f, "{err}"),
         }
     }
 }
 
-impl From<std::io::Error> for GenericError {
-    fn from(err: std::io::Error) -> Self {
-        GenericError::IOError(err)
+impl Error for ShellError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ShellError::Parse(err) => Some(err),
+            ShellError::Redirect { source, .. } => Some(source),
+            ShellError::Exec(errno) => Some(errno),
+            ShellError::Internal { source, .. } => source.as_ref().map(|err| err as &(dyn Error + 'static)),
+            ShellError::Io(err) => Some(err),
+            _ => None,
+        }
     }
 }
 
-impl From<String> for GenericError {
-    fn from(s: String) -> Self {
-        GenericError::OtherError(s)
+impl From<ParseError> for ShellError {
+    fn from(err: ParseError) -> Self {
+        ShellError::Parse(err)
     }
 }
 
-impl From<&str> for GenericError {
-    fn from(s: &str) -> Self {
-        GenericError::OtherError(s.to_string())
+impl From<io::Error> for ShellError {
+    fn from(err: io::Error) -> Self {
+        ShellError::Io(err)
     }
 }
+
+#[derive(Debug)]
+pub enum ParseErrorKind {
+    /// a token that can't appear there, e.g., `)` without `(`
+    UnexpectedToken(String),
+    /// the input ends in the middle of a command, where the reason is given, e.g., unclosed quotes
+    UnexpectedEnd(String),
+    /// any other invalid syntax, e.g., a bad substitution
+    Invalid(String),
+}
+
+/// A syntax error and where it is in the source text, which is shown with the line and a caret under the span
+#[derive(Debug)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    /// the line and the column of the beginning of the span, which count from 1,
+    /// where the line is 0 if the position is not known yet
+    pub line: usize,
+    pub column: usize,
+    /// the byte offsets in the source text
+    pub span: Range<usize>,
+    /// the line containing the beginning of the span
+    pub source_line: String,
+}
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind, text: &str, span: Range<usize>) -> Self {
+        let start = span.start.min(text.len());
+        let line_start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = text[start..].find('\n').map_or(text.len(), |i| start + i);
+        Self {
+            kind,
+            line: text[..start].matches('\n').count() + 1,
+            column: text[line_start..start].chars().count() + 1,
+            span,
+            source_line: text[line_start..line_end].to_string(),
+        }
+    }
+
+    /// Returns the error whose position is given later by the caller knowing the source text
+    pub fn unlocated(kind: ParseErrorKind) -> Self {
+        Self { kind, line: 0, column: 0, span: 0..0, source_line: String::new() }
+    }
+
+    /// Returns the message without the source line
+    pub fn message(&self) -> String {
+        match &self.kind {
+            ParseErrorKind::UnexpectedToken(token) => format!("unexpected `{token}`"),
+            ParseErrorKind::UnexpectedEnd(msg) | ParseErrorKind::Invalid(msg) => msg.clone(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            return write!(f, "{}", self.message());
+        }
+        // The caret is indented by the same tabs as the line, and spans the characters of the span on the line.
+        let indent: String = self.source_line.chars()
+            .take(self.column - 1)
+            .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+            .collect();
+        let mut width = 0;
+        let mut len = 0;
+        for ch in self.source_line.chars().skip(self.column - 1) {
+            if len >= self.span.len() {
+                break;
+            }
+            len += ch.len_utf8();
+            width += 1;
+        }
+        let underline = format!("^{}", "~".repeat(width.max(1) - 1));
+        write!(f, "{}\n  {}\n  {indent}{underline}", self.message(), self.source_line)
+    }
+}
+
+impl Error for ParseError {}
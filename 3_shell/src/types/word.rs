@@ -0,0 +1,132 @@
+use crate::types::command::CommandList;
+
+/// `${NAME<op>word}`
+#[derive(Clone)]
+pub enum ParamOp {
+    /// `$NAME` or `${NAME}`
+    None,
+    /// `${NAME:-word}`, or `${NAME-word}` if the colon is omitted,
+    /// which tests only whether the parameter is unset rather than unset or empty
+    Default { word: Word, colon: bool },
+    /// `${NAME:=word}` or `${NAME=word}`
+    Assign { word: Word, colon: bool },
+    /// `${NAME:+word}` or `${NAME+word}`
+    Alternative { word: Word, colon: bool },
+    /// `${NAME:?word}` or `${NAME?word}`
+    Error { word: Word, colon: bool },
+    /// `${#NAME}`
+    Length,
+}
+
+#[derive(Clone)]
+pub struct Param {
+    /// a variable name or a special parameter like `?`
+    pub name: String,
+    /// the subscript of `${NAME[index]}`, which is `@`, `*` or an arithmetic expression
+    pub index: Option<String>,
+    pub op: ParamOp,
+}
+
+/// A piece of a word whose value may only be known at execution time
+#[derive(Clone)]
+pub enum WordPart {
+    Literal { text: String, quoted: bool },
+    Param { param: Param, quoted: bool },
+    /// `$(list)` or `` `list` ``
+    CmdSubst { list: CommandList, quoted: bool },
+    /// `$((expr))`, where `expr` is expanded before it is evaluated
+    Arith { expr: Word, quoted: bool },
+}
+
+/// A word is kept unexpanded after parsing,
+/// so that parameters are expanded right before the command is executed.
+#[derive(Clone, Default)]
+pub struct Word {
+    pub parts: Vec<WordPart>,
+}
+
+impl Word {
+    pub fn new() -> Self {
+        Self { parts: vec![] }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    /// Appends a literal character
+    pub fn push(&mut self, ch: char, quoted: bool) {
+        match self.parts.last_mut() {
+            Some(WordPart::Literal { text, quoted: last_quoted }) if *last_quoted == quoted => text.push(ch),
+            _ => self.parts.push(WordPart::Literal { text: ch.to_string(), quoted }),
+        }
+    }
+
+    /// Begins a quoted part, so that the word is kept even if the quotes are empty, e.g., `''`
+    pub fn begin_quote(&mut self) {
+        if !matches!(self.parts.last(), Some(WordPart::Literal { quoted: true, .. })) {
+            self.parts.push(WordPart::Literal { text: String::new(), quoted: true });
+        }
+    }
+
+    pub fn push_part(&mut self, part: WordPart) {
+        self.parts.push(part);
+    }
+
+    /// Returns the text if the word is a single unquoted literal, e.g., to tell if it is a reserved word
+    pub fn as_literal(&self) -> Option<&str> {
+        match self.parts.as_slice() {
+            [WordPart::Literal { text, quoted: false }] => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Returns the number if the word is unquoted digits, like `2` in `2>file`
+    pub fn fd_number(&self) -> Option<i32> {
+        self.as_literal()
+            .filter(|text| text.chars().all(|ch| ch.is_ascii_digit()))
+            .and_then(|text| text.parse().ok())
+    }
+
+    pub fn has_cmd_subst(&self) -> bool {
+        self.parts.iter().any(|part| matches!(part, WordPart::CmdSubst { .. }))
+    }
+
+    /// Splits a word like `NAME=value` into the name and the value
+    /// if the name and `=` are neither quoted nor expanded, or gives the word back otherwise
+    pub fn into_assignment(mut self) -> Result<(String, Word), Word> {
+        let (name, value) = match self.split_assignment() {
+            Some((name, value)) => (name.to_string(), value.to_string()),
+            None => return Err(self),
+        };
+
+        let mut value_word = Word::new();
+        for ch in value.chars() {
+            value_word.push(ch, false);
+        }
+        value_word.parts.extend(self.parts.drain(1..));
+        Ok((name, value_word))
+    }
+
+    /// Whether the word is of the form `NAME=value`
+    pub fn is_assignment(&self) -> bool {
+        self.split_assignment().is_some()
+    }
+
+    /// Splits the leading literal of `NAME=value` into the name and the rest
+    fn split_assignment(&self) -> Option<(&str, &str)> {
+        match self.parts.first() {
+            Some(WordPart::Literal { text, quoted: false }) => {
+                text.split_once('=').filter(|(name, _)| is_valid_name(name))
+            },
+            _ => None,
+        }
+    }
+}
+
+/// A valid name consists of alphanumerics and underscores, and does not begin with a digit.
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(ch) if ch.is_ascii_alphabetic() || ch == '_')
+        && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
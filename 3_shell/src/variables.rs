@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::ffi::{CString, OsString};
+use std::os::unix::ffi::OsStrExt;
+
+pub struct Variable {
+    /// the value, or the first element of an array
+    pub value: String,
+    /// the elements if it is an array, e.g., `BASH_REMATCH`
+    pub elements: Option<Vec<String>>,
+    /// whether it is passed to the environment of the commands
+    pub exported: bool,
+}
+
+/// Shell variables, among which the exported ones form the environment of the commands
+#[derive(Default)]
+pub struct Variables {
+    vars: HashMap<String, Variable>,
+    /// the variables shadowed by `local` in each function being called, which are restored when it returns
+    scopes: Vec<Vec<(String, Option<Variable>)>>,
+    /// the inherited environment entries that aren't valid UTF-8,
+    /// which are passed to the commands as they are unless a variable of the same name is set
+    raw_env: Vec<(OsString, OsString)>,
+}
+
+impl Variables {
+    /// All the variables inherited from the environment are exported.
+    pub fn from_env() -> Self {
+        let mut vars = HashMap::new();
+        let mut raw_env = vec![];
+        for (name, value) in std::env::vars_os() {
+            match (name.to_str(), value.to_str()) {
+                (Some(name), Some(value)) => {
+                    vars.insert(name.to_string(), Variable { value: value.to_string(), elements: None, exported: true });
+                },
+                _ => raw_env.push((name, value)),
+            }
+        }
+        Self { vars, scopes: vec![], raw_env }
+    }
+
+    /// Removes the inherited entry that isn't valid UTF-8, and returns whether it existed
+    fn remove_raw(&mut self, name: &str) -> bool {
+        let len = self.raw_env.len();
+        self.raw_env.retain(|(raw_name, _)| raw_name.as_bytes() != name.as_bytes());
+        self.raw_env.len() < len
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.vars.get(name).map(|var| var.value.as_str())
+    }
+
+    /// Returns the element of the array, where a variable that is not an array only has the element 0
+    pub fn get_element(&self, name: &str, index: usize) -> Option<&str> {
+        let var = self.vars.get(name)?;
+        match &var.elements {
+            Some(elements) => elements.get(index).map(String::as_str),
+            None => (index == 0).then_some(var.value.as_str()),
+        }
+    }
+
+    /// Returns all the elements of the array, or the value alone if it is not an array
+    pub fn get_elements(&self, name: &str) -> Vec<String> {
+        match self.vars.get(name) {
+            Some(Variable { elements: Some(elements), .. }) => elements.clone(),
+            Some(var) => vec![var.value.clone()],
+            None => vec![],
+        }
+    }
+
+    /// Sets the value, or the first element of an array, keeping whether it is exported
+    pub fn set(&mut self, name: &str, value: String) {
+        match self.vars.get_mut(name) {
+            Some(var) => {
+                match var.elements.as_mut() {
+                    Some(elements) if elements.is_empty() => elements.push(value.clone()),
+                    Some(elements) => elements[0] = value.clone(),
+                    None => {},
+                }
+                var.value = value;
+            },
+            None => {
+                // It replaces the inherited entry, so it's exported in the same way.
+                let exported = self.remove_raw(name);
+                self.vars.insert(name.to_string(), Variable { value, elements: None, exported });
+            },
+        }
+    }
+
+    /// Makes the variable an array of the elements, keeping whether it is exported
+    pub fn set_array(&mut self, name: &str, elements: Vec<String>) {
+        let value = elements.first().cloned().unwrap_or_default();
+        let var = self.vars.entry(name.to_string())
+            .or_insert_with(|| Variable { value: String::new(), elements: None, exported: false });
+        var.value = value;
+        var.elements = Some(elements);
+    }
+
+    /// Exports the variable, which is created with an empty value if it doesn't exist
+    pub fn export(&mut self, name: &str) {
+        // the inherited entry is already exported
+        if !self.vars.contains_key(name)
+            && self.raw_env.iter().any(|(raw_name, _)| raw_name.as_bytes() == name.as_bytes()) {
+            return;
+        }
+        self.vars.entry(name.to_string())
+            .or_insert_with(|| Variable { value: String::new(), elements: None, exported: false })
+            .exported = true;
+    }
+
+    pub fn unexport(&mut self, name: &str) {
+        self.remove_raw(name);
+        if let Some(var) = self.vars.get_mut(name) {
+            var.exported = false;
+        }
+    }
+
+    pub fn unset(&mut self, name: &str) -> Option<Variable> {
+        self.remove_raw(name);
+        self.vars.remove(name)
+    }
+
+    /// Restores the variable to what `unset` or `insert` has returned
+    pub fn insert(&mut self, name: &str, var: Option<Variable>) -> Option<Variable> {
+        match var {
+            Some(var) => self.vars.insert(name.to_string(), var),
+            None => self.vars.remove(name),
+        }
+    }
+
+    /// Starts a scope for the local variables of a function
+    pub fn push_scope(&mut self) {
+        self.scopes.push(vec![]);
+    }
+
+    /// Restores the variables shadowed in the innermost scope
+    pub fn pop_scope(&mut self) {
+        for (name, var) in self.scopes.pop().unwrap_or_default().into_iter().rev() {
+            self.insert(&name, var);
+        }
+    }
+
+    /// Makes the variable local to the innermost scope if any, where it starts unset
+    pub fn make_local(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut()
+            && !scope.iter().any(|(local, _)| local == name) {
+            scope.push((name.to_string(), self.vars.remove(name)));
+        }
+    }
+
+    /// Returns the variables sorted by name
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Variable)> {
+        let mut vars: Vec<_> = self.vars.iter().collect();
+        vars.sort_by(|a, b| a.0.cmp(b.0));
+        vars.into_iter()
+    }
+
+    /// Returns the exported variables in the form of `NAME=value` along with the inherited entries not set again
+    pub fn environ(&self) -> Vec<CString> {
+        let raw_env = self.raw_env.iter()
+            .filter(|(name, _)| name.to_str().is_none_or(|name| !self.vars.contains_key(name)))
+            .filter_map(|(name, value)| CString::new([name.as_bytes(), b"=", value.as_bytes()].concat()).ok());
+        self.vars.iter()
+            .filter(|(_, var)| var.exported)
+            .filter_map(|(name, var)| CString::new(format!("{name}={}", var.value)).ok())
+            .chain(raw_env)
+            .collect()
+    }
+}